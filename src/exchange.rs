@@ -0,0 +1,37 @@
+//! The versioned JSON format used by `export json` and `import json`.
+//!
+//! The format is separate from how `time_track` stores its database, so that it stays readable
+//! when the database format changes. Changes that older versions of this program can't read
+//! increase `EXCHANGE_VERSION`.
+
+use serde::{Deserialize, Serialize};
+
+pub const EXCHANGE_FORMAT: &str = "timetrack";
+pub const EXCHANGE_VERSION: u32 = 1;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Exchange {
+    /// Always "timetrack", to recognize the file.
+    pub format: String,
+    pub version: u32,
+    pub projects: Vec<ExchangeProject>,
+    /// Sorted from the oldest checkpoint to the newest.
+    pub checkpoints: Vec<ExchangeCheckpoint>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ExchangeProject {
+    pub short_name: String,
+    pub long_name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ExchangeCheckpoint {
+    /// An RFC 3339 date and time, for instance "2021-11-26T08:00:00+01:00".
+    pub time: String,
+    /// The short name of the project, or `None` for empty checkpoints.
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub message: String,
+}
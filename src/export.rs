@@ -0,0 +1,398 @@
+//! Exporting checkpoints to formats that other tools understand.
+//!
+//! Other tools track intervals with a start and an end, so each non-empty checkpoint is exported
+//! as an interval from the previous checkpoint to it.
+
+use crate::{
+    counted_duration,
+    exchange::{Exchange, ExchangeCheckpoint, ExchangeProject, EXCHANGE_FORMAT, EXCHANGE_VERSION},
+    hm_string_from_i64, parse_time_range, read_database, Config, ICS_UTC_FORMAT,
+    TIMEWARRIOR_FORMAT, YMDHM_FORMAT,
+};
+use chrono::prelude::*;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, Write},
+    path::Path,
+};
+use time_track::{CheckpointDb, ProjectId};
+
+const TIMECLOCK_FORMAT: &str = "%Y/%m/%d %H:%M:%S";
+const ORG_FORMAT: &str = "[%Y-%m-%d %a %H:%M]";
+/// The maximum length of a line in an iCalendar file in bytes, not counting the line break.
+const ICS_LINE_LENGTH: usize = 75;
+
+pub fn export(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    if let Some(matches) = matches.subcommand_matches("intervals") {
+        intervals(matches, config)?;
+    }
+    if let Some(matches) = matches.subcommand_matches("timeclock") {
+        timeclock(matches, config)?;
+    }
+    if let Some(matches) = matches.subcommand_matches("ics") {
+        ics(matches, config)?;
+    }
+    if let Some(matches) = matches.subcommand_matches("org") {
+        org(matches, config)?;
+    }
+    if let Some(matches) = matches.subcommand_matches("json") {
+        json(matches, config)?;
+    }
+
+    Ok(())
+}
+
+/// The tracked time between a non-empty checkpoint and the checkpoint before it.
+struct Interval {
+    start: i64,
+    end: i64,
+    short_name: String,
+    long_name: String,
+    message: String,
+}
+
+/// Returns the intervals of all checkpoints with counted time between the given times, oldest
+/// first.
+fn intervals_between_times(
+    checkpoint_db: &CheckpointDb,
+    start: &DateTime<Local>,
+    end: &DateTime<Local>,
+    config: &Config,
+) -> Vec<Interval> {
+    let mut intervals: Vec<Interval> = checkpoint_db
+        .get_log_between_times(start, end)
+        .iter()
+        .filter_map(|log_checkpoint| {
+            let duration = counted_duration(checkpoint_db, log_checkpoint, config)?;
+            let (short_name, long_name) =
+                match checkpoint_db.project_from_project_id(log_checkpoint.checkpoint.project_id) {
+                    Some(project) => (project.short_name.clone(), project.long_name.clone()),
+                    None => (String::new(), String::new()),
+                };
+
+            Some(Interval {
+                start: log_checkpoint.timestamp - duration,
+                end: log_checkpoint.timestamp,
+                short_name,
+                long_name,
+                message: log_checkpoint.checkpoint.message.clone(),
+            })
+        })
+        .collect();
+
+    intervals.sort_by_key(|interval| interval.start);
+    intervals
+}
+
+/// Reads the "start" and "end" arguments, defaulting to everything up until the end of today.
+fn time_range(matches: &clap::ArgMatches) -> Option<(DateTime<Local>, DateTime<Local>)> {
+    parse_time_range(
+        matches,
+        Local.timestamp(0, 0),
+        Local::today().and_hms(23, 59, 59),
+    )
+}
+
+/// Returns the file given by the "output" argument, or stdout.
+fn output(matches: &clap::ArgMatches) -> io::Result<Box<dyn Write>> {
+    Ok(match matches.value_of("output") {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    })
+}
+
+/// An interval in the format of `timew export`.
+#[derive(Serialize)]
+struct TimewarriorInterval {
+    id: usize,
+    start: String,
+    end: String,
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    annotation: String,
+}
+
+/// Exports intervals as JSON in the format of `timew export`, with the project short name as the
+/// only tag.
+fn intervals(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    let (start, end) = match time_range(matches) {
+        Some(range) => range,
+        None => return Ok(()),
+    };
+
+    let checkpoint_db = read_database(Path::new(&config.database_path))?;
+    let intervals = intervals_between_times(&checkpoint_db, &start, &end, config);
+
+    let format_time = |timestamp| Utc.timestamp(timestamp, 0).format(TIMEWARRIOR_FORMAT);
+
+    // Timewarrior numbers intervals from the most recent one.
+    let count = intervals.len();
+    let timewarrior_intervals: Vec<TimewarriorInterval> = intervals
+        .into_iter()
+        .enumerate()
+        .map(|(i, interval)| TimewarriorInterval {
+            id: count - i,
+            start: format_time(interval.start).to_string(),
+            end: format_time(interval.end).to_string(),
+            tags: if interval.short_name.is_empty() {
+                Vec::new()
+            } else {
+                vec![interval.short_name]
+            },
+            annotation: interval.message,
+        })
+        .collect();
+
+    let mut output = output(matches)?;
+    serde_json::to_writer_pretty(&mut output, &timewarrior_intervals)?;
+    writeln!(output)?;
+
+    Ok(())
+}
+
+/// Exports clock-in and clock-out lines in the timeclock format that ledger and hledger read, with
+/// the project short names as accounts.
+fn timeclock(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    let (start, end) = match time_range(matches) {
+        Some(range) => range,
+        None => return Ok(()),
+    };
+
+    let checkpoint_db = read_database(Path::new(&config.database_path))?;
+    let account_prefix = matches.value_of("account-prefix").unwrap_or("");
+
+    let mut output = output(matches)?;
+    for interval in intervals_between_times(&checkpoint_db, &start, &end, config) {
+        let account = if interval.short_name.is_empty() {
+            "unknown"
+        } else {
+            &interval.short_name
+        };
+
+        write!(
+            output,
+            "i {} {}{}",
+            Local.timestamp(interval.start, 0).format(TIMECLOCK_FORMAT),
+            account_prefix,
+            account
+        )?;
+        if !interval.message.is_empty() {
+            // Two spaces separate the account from the description.
+            write!(output, "  {}", interval.message.replace('\n', " "))?;
+        }
+        writeln!(output)?;
+        writeln!(
+            output,
+            "o {}",
+            Local.timestamp(interval.end, 0).format(TIMECLOCK_FORMAT)
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Escapes text for use as an iCalendar property value.
+fn ics_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Writes a content line, folding it into several lines if it's too long.
+fn write_ics_line(output: &mut dyn Write, line: &str) -> io::Result<()> {
+    let mut length = 0;
+
+    for c in line.chars() {
+        if length + c.len_utf8() > ICS_LINE_LENGTH {
+            // The space at the start of a continuation line counts towards its length.
+            output.write_all(b"\r\n ")?;
+            length = 1;
+        }
+        write!(output, "{}", c)?;
+        length += c.len_utf8();
+    }
+
+    output.write_all(b"\r\n")
+}
+
+/// Exports an iCalendar file with an event for each interval, to show tracked time in calendars.
+fn ics(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    let (start, end) = match time_range(matches) {
+        Some(range) => range,
+        None => return Ok(()),
+    };
+
+    let checkpoint_db = read_database(Path::new(&config.database_path))?;
+    let format_time = |timestamp| Utc.timestamp(timestamp, 0).format(ICS_UTC_FORMAT);
+    let now = Utc::now().format(ICS_UTC_FORMAT);
+
+    let mut output = output(matches)?;
+    write_ics_line(&mut output, "BEGIN:VCALENDAR")?;
+    write_ics_line(&mut output, "VERSION:2.0")?;
+    write_ics_line(&mut output, "PRODID:-//Orsvarn//TimeTrack CLI//EN")?;
+
+    for interval in intervals_between_times(&checkpoint_db, &start, &end, config) {
+        write_ics_line(&mut output, "BEGIN:VEVENT")?;
+        // The timestamp of the checkpoint identifies the event, since there can only be one
+        // checkpoint at each point in time.
+        write_ics_line(
+            &mut output,
+            &format!("UID:{}@timetrack.orsvarn.com", interval.end),
+        )?;
+        write_ics_line(&mut output, &format!("DTSTAMP:{}", now))?;
+        write_ics_line(
+            &mut output,
+            &format!("DTSTART:{}", format_time(interval.start)),
+        )?;
+        write_ics_line(&mut output, &format!("DTEND:{}", format_time(interval.end)))?;
+        write_ics_line(
+            &mut output,
+            &format!("SUMMARY:{}", ics_escape(&interval.long_name)),
+        )?;
+        if !interval.message.is_empty() {
+            write_ics_line(
+                &mut output,
+                &format!("DESCRIPTION:{}", ics_escape(&interval.message)),
+            )?;
+        }
+        write_ics_line(&mut output, "END:VEVENT")?;
+    }
+
+    write_ics_line(&mut output, "END:VCALENDAR")
+}
+
+/// Exports an Org-mode document with a heading for each project, a heading for each message in
+/// it, and the intervals as `CLOCK` lines.
+fn org(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    let (start, end) = match time_range(matches) {
+        Some(range) => range,
+        None => return Ok(()),
+    };
+
+    let checkpoint_db = read_database(Path::new(&config.database_path))?;
+
+    // Messages are kept in the order they were first used.
+    let mut projects: BTreeMap<String, Vec<(String, Vec<Interval>)>> = BTreeMap::new();
+    for interval in intervals_between_times(&checkpoint_db, &start, &end, config) {
+        let messages = projects.entry(interval.long_name.clone()).or_default();
+        match messages
+            .iter_mut()
+            .find(|(message, _)| *message == interval.message)
+        {
+            Some((_, intervals)) => intervals.push(interval),
+            None => messages.push((interval.message.clone(), vec![interval])),
+        }
+    }
+
+    let mut output = output(matches)?;
+    for (long_name, messages) in projects {
+        let long_name = if long_name.is_empty() {
+            "Unknown project"
+        } else {
+            &long_name
+        };
+        writeln!(output, "* {}", long_name)?;
+
+        for (message, intervals) in messages {
+            let message = if message.is_empty() {
+                "No message".to_string()
+            } else {
+                message.replace('\n', " ")
+            };
+            writeln!(output, "** {}", message)?;
+            writeln!(output, "   :LOGBOOK:")?;
+            for interval in intervals.iter().rev() {
+                writeln!(
+                    output,
+                    "   CLOCK: {}--{} => {:>5}",
+                    Local.timestamp(interval.start, 0).format(ORG_FORMAT),
+                    Local.timestamp(interval.end, 0).format(ORG_FORMAT),
+                    hm_string_from_i64(interval.end - interval.start)
+                )?;
+            }
+            writeln!(output, "   :END:")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Exports the projects and the checkpoints in the versioned format that `import json` reads.
+fn json(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    let checkpoint_db = read_database(Path::new(&config.database_path))?;
+
+    // Everything is exported by default, including checkpoints in the future.
+    let first = checkpoint_db.checkpoints.keys().next().copied();
+    let last = checkpoint_db.checkpoints.keys().next_back().copied();
+    let (start, end) = match parse_time_range(
+        matches,
+        Local.timestamp(first.unwrap_or(0), 0),
+        Local.timestamp(last.unwrap_or(0), 0),
+    ) {
+        Some(range) => range,
+        None => return Ok(()),
+    };
+    let range = start.timestamp()..=end.timestamp();
+
+    // A checkpoint whose project is gone can't be imported again as it was.
+    let unknown_projects: Vec<String> = checkpoint_db
+        .checkpoints
+        .range(range.clone())
+        .filter(|(_, checkpoint)| {
+            checkpoint.project_id != ProjectId::NoId
+                && checkpoint_db
+                    .project_from_project_id(checkpoint.project_id)
+                    .is_none()
+        })
+        .map(|(timestamp, _)| {
+            Local
+                .timestamp(*timestamp, 0)
+                .format(YMDHM_FORMAT)
+                .to_string()
+        })
+        .collect();
+    if !unknown_projects.is_empty() {
+        println!(
+            "Not exporting, because the checkpoints at '{}' have unknown projects. `check` shows them",
+            unknown_projects.join("', '")
+        );
+        return Ok(());
+    }
+
+    let projects = checkpoint_db
+        .projects
+        .values()
+        .map(|project| ExchangeProject {
+            short_name: project.short_name.clone(),
+            long_name: project.long_name.clone(),
+        })
+        .collect();
+
+    let checkpoints = checkpoint_db
+        .checkpoints
+        .range(range)
+        .map(|(timestamp, checkpoint)| ExchangeCheckpoint {
+            time: Local.timestamp(*timestamp, 0).to_rfc3339(),
+            project: checkpoint_db
+                .project_from_project_id(checkpoint.project_id)
+                .map(|project| project.short_name.clone()),
+            message: checkpoint.message.clone(),
+        })
+        .collect();
+
+    let exchange = Exchange {
+        format: EXCHANGE_FORMAT.to_string(),
+        version: EXCHANGE_VERSION,
+        projects,
+        checkpoints,
+    };
+
+    let mut output = output(matches)?;
+    serde_json::to_writer_pretty(&mut output, &exchange)?;
+    writeln!(output)?;
+
+    Ok(())
+}
@@ -0,0 +1,1329 @@
+//! Importing time tracking data from other tools.
+//!
+//! Other tools track intervals with a start and an end, while this program only has checkpoints.
+//! An interval is imported as an empty checkpoint at its start and a checkpoint with the
+//! project at its end.
+
+use crate::{
+    change_verb, confirm,
+    exchange::{Exchange, EXCHANGE_FORMAT, EXCHANGE_VERSION},
+    hour_string_from_i64, parse_time_range, prompt_line, read_database, write_database, Config,
+    HM_FORMAT, ICS_LOCAL_FORMAT, ICS_UTC_FORMAT, TIMEWARRIOR_FORMAT, YMDHM_FORMAT,
+};
+use chrono::prelude::*;
+use serde::{de::IgnoredAny, Deserialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io,
+    path::Path,
+};
+use time_track::{CheckpointDb, ProjectId};
+
+/// The date formats Toggl and Clockify use, depending on the user's settings.
+const CSV_DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%m/%d/%Y", "%d.%m.%Y"];
+const CSV_TIME_FORMATS: &[&str] = &["%H:%M:%S", "%I:%M:%S %p", "%H:%M", "%I:%M %p"];
+
+pub fn import(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    if let Some(matches) = matches.subcommand_matches("timewarrior") {
+        timewarrior(matches, config)?;
+    }
+    if let Some(matches) = matches.subcommand_matches("toggl") {
+        import_csv(matches, config)?;
+    }
+    if let Some(matches) = matches.subcommand_matches("clockify") {
+        import_csv(matches, config)?;
+    }
+    if let Some(matches) = matches.subcommand_matches("watson") {
+        watson(matches, config)?;
+    }
+    if let Some(matches) = matches.subcommand_matches("ics") {
+        ics(matches, config)?;
+    }
+    if let Some(matches) = matches.subcommand_matches("json") {
+        json(matches, config)?;
+    }
+
+    Ok(())
+}
+
+/// An interval of time from another tool, with the names that will decide its project.
+struct Interval {
+    start: i64,
+    end: i64,
+    names: Vec<String>,
+    message: String,
+}
+
+/// Maps names from other tools, like tags, to project short names. Names that aren't in the
+/// mapping file are asked for and remembered for the rest of the import.
+struct ProjectMapping {
+    short_names: BTreeMap<String, String>,
+}
+
+impl ProjectMapping {
+    /// Reads a mapping file, which is a JSON object with names as keys and short names as
+    /// values. An empty short name means no project.
+    fn read(path: Option<&str>) -> io::Result<ProjectMapping> {
+        let short_names = match path {
+            Some(path) => serde_json::from_reader(File::open(path)?)?,
+            None => BTreeMap::new(),
+        };
+
+        Ok(ProjectMapping { short_names })
+    }
+
+    fn project_id(&mut self, name: &str, checkpoint_db: &CheckpointDb) -> io::Result<ProjectId> {
+        if let Some(short_name) = self.short_names.get(name) {
+            if let Some(project_id) = checkpoint_db.project_id_from_short_name(short_name) {
+                return Ok(project_id);
+            }
+            println!(
+                "The mapping for '{}' refers to a project that does not exist: '{}'",
+                name, short_name
+            );
+        }
+
+        loop {
+            let short_name = prompt_line(&format!(
+                "Project short name for '{}' (leave empty for no project): ",
+                name
+            ))?;
+
+            if let Some(project_id) = checkpoint_db.project_id_from_short_name(&short_name) {
+                self.short_names.insert(name.to_string(), short_name);
+                return Ok(project_id);
+            }
+            println!("Project with short name does not exist: '{}'", short_name);
+        }
+    }
+
+    /// Returns the project of the first name that maps to one, and the names that were not used.
+    fn resolve(
+        &mut self,
+        names: &[String],
+        checkpoint_db: &CheckpointDb,
+    ) -> io::Result<(ProjectId, Vec<String>)> {
+        let mut project_id = ProjectId::NoId;
+        let mut unused = Vec::new();
+
+        for name in names {
+            let name_project_id = self.project_id(name, checkpoint_db)?;
+            if project_id == ProjectId::NoId && name_project_id != ProjectId::NoId {
+                project_id = name_project_id;
+            } else {
+                unused.push(name.clone());
+            }
+        }
+
+        Ok((project_id, unused))
+    }
+}
+
+/// Returns true if the interval can't be added without changing existing checkpoints.
+fn overlaps(checkpoint_db: &CheckpointDb, start: i64, end: i64) -> bool {
+    if checkpoint_db
+        .checkpoints
+        .range(start + 1..=end)
+        .next()
+        .is_some()
+    {
+        return true;
+    }
+
+    // The interval would cut a piece out of the span of the checkpoint after it. When that is the
+    // first checkpoint, it would instead get a span from the end of the interval, which is time
+    // that was never tracked.
+    match checkpoint_db.checkpoints.range(end + 1..).next() {
+        Some((_, next)) => next.project_id != ProjectId::NoId,
+        None => false,
+    }
+}
+
+/// Adds the intervals to the database, skipping the ones that overlap existing checkpoints, and
+/// writes it unless it's a dry run.
+fn add_intervals(
+    intervals: &[Interval],
+    mapping: &mut ProjectMapping,
+    checkpoint_db: &mut CheckpointDb,
+    config: &Config,
+) -> io::Result<()> {
+    let mut imported = 0;
+    let mut duration = 0;
+
+    for interval in intervals {
+        let time_str = Local.timestamp(interval.start, 0).format(YMDHM_FORMAT);
+
+        if interval.end <= interval.start {
+            println!("Skipping interval at '{}', it has no duration", time_str);
+            continue;
+        }
+        if overlaps(checkpoint_db, interval.start, interval.end) {
+            println!(
+                "Skipping interval at '{}', it overlaps existing checkpoints",
+                time_str
+            );
+            continue;
+        }
+
+        let (project_id, unused_names) = mapping.resolve(&interval.names, checkpoint_db)?;
+        let message = if interval.message.is_empty() {
+            unused_names.join(", ")
+        } else {
+            interval.message.clone()
+        };
+
+        if !checkpoint_db.checkpoints.contains_key(&interval.start) {
+            checkpoint_db
+                .add_checkpoint(interval.start, "", ProjectId::NoId)
+                .unwrap();
+        }
+        checkpoint_db
+            .add_checkpoint(interval.end, &message, project_id)
+            .unwrap();
+
+        imported += 1;
+        if project_id != ProjectId::NoId {
+            duration += interval.end - interval.start;
+        }
+    }
+
+    if config.dry_run {
+        println!(
+            "Would import {} of {} intervals ({}h), nothing was written",
+            imported,
+            intervals.len(),
+            hour_string_from_i64(duration)
+        );
+    } else {
+        write_database(checkpoint_db, config)?;
+        println!(
+            "Imported {} of {} intervals ({}h)",
+            imported,
+            intervals.len(),
+            hour_string_from_i64(duration)
+        );
+    }
+
+    Ok(())
+}
+
+fn parse_timewarrior_time(s: &str) -> Option<i64> {
+    NaiveDateTime::parse_from_str(s, TIMEWARRIOR_FORMAT)
+        .ok()
+        .map(|dt| dt.timestamp())
+}
+
+/// An interval as written by `timew export`.
+#[derive(Deserialize)]
+struct TimewarriorInterval {
+    start: String,
+    end: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    annotation: String,
+}
+
+/// A frame from Watson's frames file: start, stop, project, id, tags and the time it was last
+/// updated.
+type WatsonFrame = (i64, i64, String, IgnoredAny, Vec<String>, IgnoredAny);
+
+/// Splits a line into whitespace separated words, keeping quoted strings together. The bool is
+/// true for quoted words.
+fn split_quoted(line: &str) -> Vec<(String, bool)> {
+    let mut words = Vec::new();
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        let mut word = String::new();
+        if c == '"' {
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => word.extend(chars.next()),
+                    '"' => break,
+                    c => word.push(c),
+                }
+            }
+            words.push((word, true));
+        } else {
+            word.push(c);
+            for c in chars.by_ref() {
+                if c.is_whitespace() {
+                    break;
+                }
+                word.push(c);
+            }
+            words.push((word, false));
+        }
+    }
+
+    words
+}
+
+/// Parses a line from a Timewarrior data file, for instance
+/// `inc 20211126T080000Z - 20211126T090000Z # tag "other tag" # "annotation"`.
+fn parse_timewarrior_line(line: &str) -> Option<Interval> {
+    let words = split_quoted(line);
+    let mut words = words.iter().map(|(word, quoted)| (word.as_str(), *quoted));
+
+    if words.next()? != ("inc", false) {
+        return None;
+    }
+    let start = parse_timewarrior_time(words.next()?.0)?;
+    if words.next()? != ("-", false) {
+        // Intervals that are still open are not imported.
+        return None;
+    }
+    let end = parse_timewarrior_time(words.next()?.0)?;
+
+    let mut sections = vec![Vec::new()];
+    for (word, quoted) in words {
+        if word == "#" && !quoted {
+            sections.push(Vec::new());
+        } else {
+            sections.last_mut().unwrap().push(word.to_string());
+        }
+    }
+
+    Some(Interval {
+        start,
+        end,
+        names: sections.get(1).cloned().unwrap_or_default(),
+        message: sections.get(2).map(|a| a.join(" ")).unwrap_or_default(),
+    })
+}
+
+fn read_timewarrior_file(path: &Path) -> io::Result<Vec<Interval>> {
+    let content = fs::read_to_string(path)?;
+
+    if content.trim_start().starts_with('[') {
+        let exported: Vec<TimewarriorInterval> = serde_json::from_str(&content)?;
+        Ok(exported
+            .into_iter()
+            .filter_map(|interval| {
+                Some(Interval {
+                    start: parse_timewarrior_time(&interval.start)?,
+                    end: parse_timewarrior_time(&interval.end?)?,
+                    names: interval.tags,
+                    message: interval.annotation,
+                })
+            })
+            .collect())
+    } else {
+        Ok(content.lines().filter_map(parse_timewarrior_line).collect())
+    }
+}
+
+/// Imports intervals from Timewarrior data files, a Timewarrior data directory, or the output of
+/// `timew export`.
+fn timewarrior(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    let mut intervals = Vec::new();
+
+    // I can unwrap this because it's required in Clap.
+    for file in matches.values_of("file").unwrap() {
+        let path = Path::new(file);
+
+        if path.is_dir() {
+            for entry in fs::read_dir(path)? {
+                let entry_path = entry?.path();
+                if entry_path.extension().is_some_and(|e| e == "data") {
+                    intervals.append(&mut read_timewarrior_file(&entry_path)?);
+                }
+            }
+        } else {
+            intervals.append(&mut read_timewarrior_file(path)?);
+        }
+    }
+
+    intervals.sort_by_key(|interval| interval.start);
+
+    let mut mapping = ProjectMapping::read(matches.value_of("map"))?;
+    let mut checkpoint_db = read_database(Path::new(&config.database_path))?;
+    add_intervals(&intervals, &mut mapping, &mut checkpoint_db, config)
+}
+
+/// Imports frames from Watson's frames file, with the tags as message.
+fn watson(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    // I can unwrap this because it's required in Clap.
+    let file = File::open(matches.value_of("file").unwrap())?;
+    let frames: Vec<WatsonFrame> = serde_json::from_reader(file)?;
+
+    let mut intervals: Vec<Interval> = frames
+        .into_iter()
+        .map(|(start, end, project, _, tags, _)| Interval {
+            start,
+            end,
+            names: vec![project],
+            message: tags.join(", "),
+        })
+        .collect();
+    intervals.sort_by_key(|interval| interval.start);
+
+    let mut mapping = ProjectMapping::read(matches.value_of("map"))?;
+    let mut checkpoint_db = read_database(Path::new(&config.database_path))?;
+    add_intervals(&intervals, &mut mapping, &mut checkpoint_db, config)
+}
+
+/// Parses comma separated values, with support for quoted fields.
+fn parse_csv(content: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = content.trim_start_matches('\u{feff}').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => record.push(std::mem::take(&mut field)),
+            ('\r', false) => (),
+            ('\n', false) => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (c, _) => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    records
+}
+
+fn parse_csv_datetime(date: &str, time: &str, date_format: Option<&str>) -> Option<i64> {
+    let date = match date_format {
+        Some(format) => NaiveDate::parse_from_str(date, format).ok()?,
+        None => CSV_DATE_FORMATS
+            .iter()
+            .find_map(|format| NaiveDate::parse_from_str(date, format).ok())?,
+    };
+    let time = CSV_TIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(time, format).ok())?;
+
+    Local
+        .from_local_datetime(&date.and_time(time))
+        .earliest()
+        .map(|dt| dt.timestamp())
+}
+
+/// Returns a short name for the long name that no project has yet, made from the initials of
+/// its words.
+fn generate_short_name(long_name: &str, checkpoint_db: &CheckpointDb) -> String {
+    let words: Vec<&str> = long_name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    let mut short_name: String = if words.len() > 1 {
+        words
+            .iter()
+            .filter_map(|word| word.chars().next())
+            .collect()
+    } else {
+        long_name
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .take(3)
+            .collect()
+    };
+    short_name = short_name.to_lowercase();
+    if short_name.is_empty() {
+        short_name = "project".to_string();
+    }
+
+    let mut candidate = short_name.clone();
+    let mut suffix = 2;
+    while checkpoint_db
+        .project_id_from_short_name(&candidate)
+        .is_some()
+    {
+        candidate = format!("{}{}", short_name, suffix);
+        suffix += 1;
+    }
+
+    candidate
+}
+
+/// Imports the detailed CSV export of Toggl or Clockify, creating projects that don't exist yet.
+fn import_csv(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    // I can unwrap this because it's required in Clap.
+    let records = parse_csv(&fs::read_to_string(matches.value_of("file").unwrap())?);
+    let mut records = records.iter();
+
+    let header: Vec<String> = match records.next() {
+        Some(h) => h.iter().map(|name| name.trim().to_lowercase()).collect(),
+        None => {
+            println!("The file is empty");
+            return Ok(());
+        }
+    };
+    let column = |name: &str| header.iter().position(|h| h == name);
+
+    let (start_date, start_time, end_date, end_time) = match (
+        column("start date"),
+        column("start time"),
+        column("end date"),
+        column("end time"),
+    ) {
+        (Some(sd), Some(st), Some(ed), Some(et)) => (sd, st, ed, et),
+        _ => {
+            println!("Could not find the start and end columns, is this a detailed export?");
+            return Ok(());
+        }
+    };
+    let project = column("project");
+    let description = column("description");
+    let tags = column("tags");
+
+    let mut checkpoint_db = read_database(Path::new(&config.database_path))?;
+    let mut mapping = ProjectMapping::read(None)?;
+    let mut intervals = Vec::new();
+
+    for record in records {
+        let field =
+            |index: Option<usize>| index.and_then(|i| record.get(i)).map_or("", |f| f.trim());
+
+        let (start, end) = match (
+            parse_csv_datetime(
+                field(Some(start_date)),
+                field(Some(start_time)),
+                matches.value_of("date-format"),
+            ),
+            parse_csv_datetime(
+                field(Some(end_date)),
+                field(Some(end_time)),
+                matches.value_of("date-format"),
+            ),
+        ) {
+            (Some(start), Some(end)) => (start, end),
+            _ => {
+                if record.iter().any(|f| !f.trim().is_empty()) {
+                    println!("Skipping a row with unreadable times: {}", record.join(","));
+                }
+                continue;
+            }
+        };
+
+        let long_name = field(project);
+        if !long_name.is_empty() && !mapping.short_names.contains_key(long_name) {
+            let existing = checkpoint_db
+                .projects
+                .values()
+                .find(|project| project.long_name == long_name)
+                .map(|project| project.short_name.clone());
+
+            let short_name = match existing {
+                Some(short_name) => short_name,
+                None => {
+                    let short_name = generate_short_name(long_name, &checkpoint_db);
+                    if let Err(e) = checkpoint_db.add_project(long_name, &short_name) {
+                        println!(
+                            "Could not add project with short name '{short}': {error}",
+                            short = short_name,
+                            error = e,
+                        );
+                        return Ok(());
+                    }
+                    if config.dry_run {
+                        println!(
+                            "Would add project '{}' with short name '{}'",
+                            long_name, short_name
+                        );
+                    } else {
+                        println!(
+                            "Added project '{}' with short name '{}'",
+                            long_name, short_name
+                        );
+                    }
+                    short_name
+                }
+            };
+            mapping
+                .short_names
+                .insert(long_name.to_string(), short_name);
+        }
+
+        let mut message = field(description).to_string();
+        if !field(tags).is_empty() {
+            if !message.is_empty() {
+                message.push(' ');
+            }
+            message.push_str(&format!("[{}]", field(tags)));
+        }
+
+        intervals.push(Interval {
+            start,
+            end,
+            names: if long_name.is_empty() {
+                Vec::new()
+            } else {
+                vec![long_name.to_string()]
+            },
+            message,
+        });
+    }
+
+    intervals.sort_by_key(|interval| interval.start);
+    add_intervals(&intervals, &mut mapping, &mut checkpoint_db, config)
+}
+
+/// A rule that maps calendar events to a project, by category or by a part of the summary.
+#[derive(Deserialize)]
+struct IcsRule {
+    category: Option<String>,
+    summary: Option<String>,
+    project: String,
+}
+
+impl IcsRule {
+    fn matches(&self, event: &IcsEvent) -> bool {
+        let category_matches = self.category.as_ref().is_none_or(|category| {
+            event
+                .categories
+                .iter()
+                .any(|c| c.eq_ignore_ascii_case(category))
+        });
+        let summary_matches = self.summary.as_ref().is_none_or(|summary| {
+            event
+                .summary
+                .to_lowercase()
+                .contains(&summary.to_lowercase())
+        });
+
+        (self.category.is_some() || self.summary.is_some()) && category_matches && summary_matches
+    }
+}
+
+#[derive(Default)]
+struct IcsEvent {
+    start: Option<i64>,
+    end: Option<i64>,
+    duration: Option<i64>,
+    summary: String,
+    categories: Vec<String>,
+    /// The TZID of a start or end time whose time zone couldn't be worked out.
+    unknown_time_zone: Option<String>,
+}
+
+/// A time zone from a VTIMEZONE component.
+#[derive(Default)]
+struct IcsTimeZone {
+    observances: Vec<IcsObservance>,
+}
+
+/// A STANDARD or DAYLIGHT part of a time zone, which says what the offset from UTC is from a
+/// point in time, and possibly from the same day every year after it.
+#[derive(Default)]
+struct IcsObservance {
+    start: Option<NaiveDateTime>,
+    /// The offset from UTC in seconds.
+    offset: Option<i64>,
+    /// The month, week and weekday of a yearly RRULE, like (3, -1, Sun) for the last Sunday in
+    /// March.
+    yearly: Option<(u32, i64, Weekday)>,
+}
+
+impl IcsObservance {
+    /// Returns the most recent time this observance started, at or before the given local time.
+    fn latest_start(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        let start = self.start?;
+
+        match self.yearly {
+            None => Some(start).filter(|start| *start <= time),
+            Some((month, week, weekday)) => (start.year()..=time.year()).rev().find_map(|year| {
+                let onset =
+                    nth_weekday_of_month(year, month, week, weekday)?.and_time(start.time());
+                Some(onset).filter(|onset| *onset <= time && *onset >= start)
+            }),
+        }
+    }
+}
+
+impl IcsTimeZone {
+    /// Returns the offset from UTC in seconds at the given local time, if the time zone
+    /// describes it.
+    fn offset_at(&self, time: NaiveDateTime) -> Option<i64> {
+        self.observances
+            .iter()
+            .filter_map(|observance| Some((observance.latest_start(time)?, observance.offset?)))
+            .max_by_key(|(start, _)| *start)
+            .map(|(_, offset)| offset)
+    }
+}
+
+/// Returns the date of the nth weekday of a month, counting from the end of the month when n is
+/// negative.
+fn nth_weekday_of_month(year: i32, month: u32, n: i64, weekday: Weekday) -> Option<NaiveDate> {
+    let weekday = weekday.num_days_from_monday() as i64;
+
+    let date = if n > 0 {
+        let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+        let days = (7 + weekday - first.weekday().num_days_from_monday() as i64) % 7;
+        first + chrono::Duration::days(days + 7 * (n - 1))
+    } else if n < 0 {
+        let next_month = if month == 12 {
+            NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+        } else {
+            NaiveDate::from_ymd_opt(year, month + 1, 1)?
+        };
+        let last = next_month.pred();
+        let days = (7 + last.weekday().num_days_from_monday() as i64 - weekday) % 7;
+        last - chrono::Duration::days(days + 7 * (-n - 1))
+    } else {
+        return None;
+    };
+
+    Some(date).filter(|date| date.month() == month)
+}
+
+/// Parses a UTC offset like `+0100` or `-053000` to seconds.
+fn parse_ics_offset(value: &str) -> Option<i64> {
+    let sign = match value.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let digits = &value[1..];
+    let hours: i64 = digits.get(0..2)?.parse().ok()?;
+    let minutes: i64 = digits.get(2..4)?.parse().ok()?;
+    let seconds: i64 = match digits.get(4..6) {
+        Some(s) => s.parse().ok()?,
+        None => 0,
+    };
+
+    Some(sign * (hours * 60 * 60 + minutes * 60 + seconds))
+}
+
+/// Parses the yearly RRULE that time zones use to repeat their observances, like
+/// `FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU`. Other rules return `None`.
+fn parse_ics_yearly_rule(value: &str) -> Option<(u32, i64, Weekday)> {
+    let mut yearly = false;
+    let mut month = None;
+    let mut day = None;
+
+    for part in value.split(';') {
+        match part.split_once('=')? {
+            ("FREQ", frequency) => yearly = frequency == "YEARLY",
+            ("BYMONTH", m) => month = m.parse::<u32>().ok(),
+            ("BYDAY", d) => {
+                let (week, weekday) = d.split_at(d.len().checked_sub(2)?);
+                let weekday = match weekday {
+                    "MO" => Weekday::Mon,
+                    "TU" => Weekday::Tue,
+                    "WE" => Weekday::Wed,
+                    "TH" => Weekday::Thu,
+                    "FR" => Weekday::Fri,
+                    "SA" => Weekday::Sat,
+                    "SU" => Weekday::Sun,
+                    _ => return None,
+                };
+                day = Some((week.trim_start_matches('+').parse::<i64>().ok()?, weekday));
+            }
+            _ => (),
+        }
+    }
+
+    let (week, weekday) = day?;
+    if yearly {
+        Some((month?, week, weekday))
+    } else {
+        None
+    }
+}
+
+fn ics_unescape(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') | Some('N') => unescaped.push('\n'),
+                Some(c) => unescaped.push(c),
+                None => (),
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+
+    unescaped
+}
+
+/// Parses a DATE-TIME value. Times in UTC end with a 'Z', times with a TZID parameter are in a
+/// time zone described in the file, and other times are read as local time. Dates without a time,
+/// used for whole day events, return `Ok(None)`. If the time zone isn't described in the file, or
+/// in a way that can't be read, its TZID is returned as the error.
+fn parse_ics_time(
+    value: &str,
+    tzid: Option<&str>,
+    time_zones: &BTreeMap<String, IcsTimeZone>,
+) -> Result<Option<i64>, String> {
+    if value.ends_with('Z') {
+        return Ok(NaiveDateTime::parse_from_str(value, ICS_UTC_FORMAT)
+            .ok()
+            .map(|dt| dt.timestamp()));
+    }
+
+    let naive = match NaiveDateTime::parse_from_str(value, ICS_LOCAL_FORMAT) {
+        Ok(naive) => naive,
+        Err(_) => return Ok(None),
+    };
+
+    match tzid {
+        Some(tzid) => match time_zones
+            .get(tzid)
+            .and_then(|time_zone| time_zone.offset_at(naive))
+        {
+            Some(offset) => Ok(Some(naive.timestamp() - offset)),
+            None => Err(tzid.to_string()),
+        },
+        None => Ok(Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|dt| dt.timestamp())),
+    }
+}
+
+/// Parses a DURATION value like `PT1H30M` or `P1D` to seconds.
+fn parse_ics_duration(value: &str) -> Option<i64> {
+    let value = value.strip_prefix('P')?;
+    let mut seconds = 0;
+    let mut number = String::new();
+
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => (),
+            unit => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                seconds += n * match unit {
+                    'W' => 7 * 24 * 60 * 60,
+                    'D' => 24 * 60 * 60,
+                    'H' => 60 * 60,
+                    'M' => 60,
+                    'S' => 1,
+                    _ => return None,
+                };
+            }
+        }
+    }
+
+    Some(seconds)
+}
+
+/// Splits a content line into its name, parameters and value, for instance
+/// `DTSTART;TZID="Europe/Stockholm":20211126T080000`.
+fn split_ics_line(line: &str) -> Option<(&str, &str, &str)> {
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+
+    let (name, params) = line[..colon]
+        .split_once(';')
+        .unwrap_or((&line[..colon], ""));
+    Some((name, params, &line[colon + 1..]))
+}
+
+/// Returns the value of a parameter, like "Europe/Stockholm" for `TZID` in
+/// `TZID="Europe/Stockholm"`.
+fn ics_param<'a>(params: &'a str, name: &str) -> Option<&'a str> {
+    params.split(';').find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        if key.eq_ignore_ascii_case(name) {
+            Some(value.trim_matches('"'))
+        } else {
+            None
+        }
+    })
+}
+
+/// Reads the VTIMEZONE components, by their TZID.
+fn parse_ics_time_zones(lines: &[String]) -> BTreeMap<String, IcsTimeZone> {
+    let mut time_zones = BTreeMap::new();
+    let mut time_zone: Option<(String, IcsTimeZone)> = None;
+    let mut observance: Option<IcsObservance> = None;
+
+    for line in lines {
+        let (name, _, value) = match split_ics_line(line) {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let value_upper = value.to_uppercase();
+
+        match (name.to_uppercase().as_str(), observance.as_mut()) {
+            ("BEGIN", None) if value_upper == "VTIMEZONE" => {
+                time_zone = Some((String::new(), IcsTimeZone::default()))
+            }
+            ("END", None) if value_upper == "VTIMEZONE" => {
+                if let Some((tzid, time_zone)) = time_zone.take() {
+                    time_zones.insert(tzid, time_zone);
+                }
+            }
+            ("BEGIN", None)
+                if time_zone.is_some()
+                    && (value_upper == "STANDARD" || value_upper == "DAYLIGHT") =>
+            {
+                observance = Some(IcsObservance::default())
+            }
+            ("END", Some(_)) => {
+                if let (Some(o), Some((_, time_zone))) = (observance.take(), time_zone.as_mut()) {
+                    time_zone.observances.push(o);
+                }
+            }
+            ("TZID", None) => {
+                if let Some((tzid, _)) = time_zone.as_mut() {
+                    *tzid = value.to_string();
+                }
+            }
+            ("DTSTART", Some(o)) => {
+                o.start = NaiveDateTime::parse_from_str(value, ICS_LOCAL_FORMAT).ok()
+            }
+            ("TZOFFSETTO", Some(o)) => o.offset = parse_ics_offset(value),
+            ("RRULE", Some(o)) => o.yearly = parse_ics_yearly_rule(value),
+            _ => (),
+        }
+    }
+
+    time_zones
+}
+
+fn parse_ics(content: &str) -> Vec<IcsEvent> {
+    // Long lines are folded by breaking them and starting the next line with whitespace.
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        match (
+            line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')),
+            lines.last_mut(),
+        ) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let time_zones = parse_ics_time_zones(&lines);
+    let mut events = Vec::new();
+    let mut event: Option<IcsEvent> = None;
+    // The number of components, like alarms, that the current line is nested in inside the event.
+    // Their properties don't belong to the event.
+    let mut depth = 0;
+
+    for line in &lines {
+        let (name, params, value) = match split_ics_line(line) {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let time = || parse_ics_time(value, ics_param(params, "TZID"), &time_zones);
+
+        match (name.to_uppercase().as_str(), event.as_mut()) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VEVENT") => {
+                event = Some(IcsEvent::default())
+            }
+            ("BEGIN", Some(_)) => depth += 1,
+            ("END", Some(_)) if depth > 0 => depth -= 1,
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VEVENT") => events.extend(event.take()),
+            (_, Some(_)) if depth > 0 => (),
+            ("DTSTART", Some(e)) => match time() {
+                Ok(start) => e.start = start,
+                Err(tzid) => e.unknown_time_zone = Some(tzid),
+            },
+            ("DTEND", Some(e)) => match time() {
+                Ok(end) => e.end = end,
+                Err(tzid) => e.unknown_time_zone = Some(tzid),
+            },
+            ("DURATION", Some(e)) => e.duration = parse_ics_duration(value),
+            ("SUMMARY", Some(e)) => e.summary = ics_unescape(value),
+            ("CATEGORIES", Some(e)) => e.categories.extend(
+                value
+                    .split(',')
+                    .map(|category| ics_unescape(category.trim())),
+            ),
+            _ => (),
+        }
+    }
+
+    events
+}
+
+/// Imports events from an iCalendar file, using the rules to choose projects and asking for the
+/// events that no rule matches.
+fn ics(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    let (range_start, range_end) = match parse_time_range(
+        matches,
+        Local::today().and_hms(00, 00, 00),
+        Local::today().and_hms(23, 59, 59),
+    ) {
+        Some(range) => range,
+        None => return Ok(()),
+    };
+
+    let rules: Vec<IcsRule> = match matches.value_of("rules") {
+        Some(path) => serde_json::from_reader(File::open(path)?)?,
+        None => Vec::new(),
+    };
+    let yes = matches.is_present("yes");
+
+    // I can unwrap this because it's required in Clap.
+    let mut events: Vec<(i64, i64, IcsEvent)> =
+        parse_ics(&fs::read_to_string(matches.value_of("file").unwrap())?)
+            .into_iter()
+            .filter(|event| match &event.unknown_time_zone {
+                Some(tzid) => {
+                    println!(
+                        "Skipping '{}', its time zone '{}' is not described in the file",
+                        event.summary, tzid
+                    );
+                    false
+                }
+                None => true,
+            })
+            .filter_map(|event| {
+                let start = event.start?;
+                let end = event.end.or_else(|| Some(start + event.duration?))?;
+                Some((start, end, event))
+            })
+            .filter(|(start, end, _)| {
+                *start >= range_start.timestamp() && *end <= range_end.timestamp()
+            })
+            .collect();
+    events.sort_by_key(|(start, _, _)| *start);
+
+    let mut checkpoint_db = read_database(Path::new(&config.database_path))?;
+
+    // Events are added with the chosen short name as their only name, so the mapping only has to
+    // map short names to themselves.
+    let mut mapping = ProjectMapping::read(None)?;
+    let mut answers: BTreeMap<String, String> = BTreeMap::new();
+    let mut intervals = Vec::new();
+
+    for (start, end, event) in events {
+        let description = format!(
+            "'{}' at {}-{}",
+            event.summary,
+            Local.timestamp(start, 0).format(YMDHM_FORMAT),
+            Local.timestamp(end, 0).format(HM_FORMAT)
+        );
+
+        let short_name = match rules.iter().find(|rule| rule.matches(&event)) {
+            Some(rule) => {
+                if !yes && !confirm(&format!("Import {} to '{}'?", description, rule.project))? {
+                    continue;
+                }
+                rule.project.clone()
+            }
+            None if yes => continue,
+            None => match answers.get(&event.summary) {
+                Some(short_name) => short_name.clone(),
+                None => {
+                    let short_name = prompt_line(&format!(
+                        "Project short name for {} (leave empty to skip): ",
+                        description
+                    ))?;
+                    answers.insert(event.summary.clone(), short_name.clone());
+                    short_name
+                }
+            },
+        };
+
+        if short_name.is_empty() {
+            continue;
+        }
+
+        mapping
+            .short_names
+            .insert(short_name.clone(), short_name.clone());
+        intervals.push(Interval {
+            start,
+            end,
+            names: vec![short_name],
+            message: event.summary,
+        });
+    }
+
+    add_intervals(&intervals, &mut mapping, &mut checkpoint_db, config)
+}
+
+/// Imports a file written by `export json`, either merging it into the database or replacing
+/// the whole database with it.
+fn json(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    // I can unwrap this because it's required in Clap.
+    let file = File::open(matches.value_of("file").unwrap())?;
+    let exchange: Exchange = serde_json::from_reader(file)?;
+
+    if exchange.format != EXCHANGE_FORMAT {
+        println!("The file is not a TimeTrack export");
+        return Ok(());
+    }
+    if exchange.version > EXCHANGE_VERSION {
+        println!(
+            "The file has version {} of the format, but only version {} and older can be read, update this program to import it",
+            exchange.version, EXCHANGE_VERSION
+        );
+        return Ok(());
+    }
+
+    let mut checkpoint_db = read_database(Path::new(&config.database_path))?;
+
+    if matches.value_of("mode") == Some("replace") {
+        checkpoint_db.checkpoints.clear();
+        checkpoint_db.projects.clear();
+    }
+
+    for project in &exchange.projects {
+        match checkpoint_db
+            .projects
+            .values()
+            .find(|p| p.short_name == project.short_name)
+        {
+            Some(existing) if existing.long_name != project.long_name => println!(
+                "Keeping the existing long name '{}' for project '{}' instead of '{}'",
+                existing.long_name, project.short_name, project.long_name
+            ),
+            Some(_) => (),
+            None => {
+                if let Err(e) = checkpoint_db.add_project(&project.long_name, &project.short_name) {
+                    println!(
+                        "Could not add project with short name '{short}': {error}",
+                        short = project.short_name,
+                        error = e,
+                    );
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    let mut imported = 0;
+    let mut existing = 0;
+    for checkpoint in &exchange.checkpoints {
+        let timestamp = match DateTime::parse_from_rfc3339(&checkpoint.time) {
+            Ok(dt) => dt.timestamp(),
+            Err(e) => {
+                println!(
+                    "Skipping checkpoint with invalid time '{}': {}",
+                    checkpoint.time, e
+                );
+                continue;
+            }
+        };
+        let short_name = checkpoint.project.as_deref().unwrap_or("");
+        let project_id = match checkpoint_db.project_id_from_short_name(short_name) {
+            Some(p) => p,
+            None => {
+                println!(
+                    "Skipping checkpoint at '{}', its project is not in the file: '{}'",
+                    checkpoint.time, short_name
+                );
+                continue;
+            }
+        };
+
+        if let Some(current) = checkpoint_db.checkpoints.get(&timestamp) {
+            if current.project_id == project_id && current.message == checkpoint.message {
+                existing += 1;
+            } else {
+                println!(
+                    "Skipping checkpoint at '{}', there already is a different checkpoint at that time",
+                    checkpoint.time
+                );
+            }
+            continue;
+        }
+
+        checkpoint_db
+            .add_checkpoint(timestamp, &checkpoint.message, project_id)
+            .unwrap();
+        imported += 1;
+    }
+
+    write_database(&checkpoint_db, config)?;
+    println!(
+        "{} {} of {} checkpoints, {} were already in the database",
+        change_verb(config, "Imported", "Would import"),
+        imported,
+        exchange.checkpoints.len(),
+        existing
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NEW_YORK_ICS: &str = "BEGIN:VCALENDAR\r
+BEGIN:VTIMEZONE\r
+TZID:America/New_York\r
+BEGIN:DAYLIGHT\r
+TZOFFSETFROM:-0500\r
+TZOFFSETTO:-0400\r
+DTSTART:20070311T020000\r
+RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=2SU\r
+END:DAYLIGHT\r
+BEGIN:STANDARD\r
+TZOFFSETFROM:-0400\r
+TZOFFSETTO:-0500\r
+DTSTART:20071104T020000\r
+RRULE:FREQ=YEARLY;BYMONTH=11;BYDAY=1SU\r
+END:STANDARD\r
+END:VTIMEZONE\r
+BEGIN:VEVENT\r
+SUMMARY:Summer meeting\r
+DTSTART;TZID=America/New_York:20210715T100000\r
+DTEND;TZID=America/New_York:20210715T110000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Winter meeting\r
+DTSTART;TZID=\"America/New_York\":20211201T100000\r
+DURATION:PT1H\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Elsewhere\r
+DTSTART;TZID=Europe/Stockholm:20211201T100000\r
+DURATION:PT1H\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    #[test]
+    fn ics_times_use_the_time_zone_of_the_file() {
+        let events = parse_ics(NEW_YORK_ICS);
+        let utc = |s| {
+            Utc.datetime_from_str(s, "%Y-%m-%d %H:%M")
+                .unwrap()
+                .timestamp()
+        };
+
+        assert_eq!(events[0].start, Some(utc("2021-07-15 14:00")));
+        assert_eq!(events[0].end, Some(utc("2021-07-15 15:00")));
+        assert_eq!(events[1].start, Some(utc("2021-12-01 15:00")));
+        assert_eq!(
+            events[2].unknown_time_zone.as_deref(),
+            Some("Europe/Stockholm")
+        );
+    }
+
+    #[test]
+    fn ics_alarm_properties_are_not_used_for_the_event() {
+        let events = parse_ics(
+            "BEGIN:VCALENDAR\r
+BEGIN:VEVENT\r
+SUMMARY:Meeting\r
+DTSTART:20211201T100000Z\r
+DURATION:PT1H\r
+BEGIN:VALARM\r
+ACTION:DISPLAY\r
+SUMMARY:Reminder\r
+DURATION:PT15M\r
+END:VALARM\r
+END:VEVENT\r
+END:VCALENDAR\r
+",
+        );
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].summary, "Meeting");
+        assert_eq!(events[0].duration, Some(60 * 60));
+    }
+
+    #[test]
+    fn split_quoted_keeps_quoted_words_together() {
+        assert_eq!(
+            split_quoted(r#"tag  "other tag" "say \"hi\"" """#),
+            vec![
+                ("tag".to_string(), false),
+                ("other tag".to_string(), true),
+                ("say \"hi\"".to_string(), true),
+                ("".to_string(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn timewarrior_line_has_tags_and_annotation() {
+        let interval = parse_timewarrior_line(
+            r#"inc 20211126T080000Z - 20211126T090000Z # tag "other tag" # "the \"annotation\"""#,
+        )
+        .unwrap();
+
+        assert_eq!(interval.start, 1_637_913_600);
+        assert_eq!(interval.end, 1_637_917_200);
+        assert_eq!(interval.names, vec!["tag", "other tag"]);
+        assert_eq!(interval.message, r#"the "annotation""#);
+    }
+
+    #[test]
+    fn timewarrior_line_without_tags() {
+        let interval = parse_timewarrior_line("inc 20211126T080000Z - 20211126T090000Z").unwrap();
+
+        assert!(interval.names.is_empty());
+        assert_eq!(interval.message, "");
+    }
+
+    #[test]
+    fn open_timewarrior_interval_is_not_parsed() {
+        assert!(parse_timewarrior_line("inc 20211126T080000Z # tag").is_none());
+        assert!(parse_timewarrior_line("inc 20211126T080000Z").is_none());
+    }
+
+    #[test]
+    fn csv_fields_can_be_quoted() {
+        let records = parse_csv(
+            "\u{feff}Project,Description\r\n\"Web, site\",\"Say \"\"hi\"\"\"\r\nOther,\"Two\nlines\"",
+        );
+
+        assert_eq!(
+            records,
+            vec![
+                vec!["Project", "Description"],
+                vec!["Web, site", r#"Say "hi""#],
+                vec!["Other", "Two\nlines"],
+            ]
+        );
+    }
+
+    fn database_with_checkpoint(short_name: &str) -> CheckpointDb {
+        let mut checkpoint_db = CheckpointDb::default();
+        checkpoint_db.add_project("Project", "p").unwrap();
+        let project_id = checkpoint_db
+            .project_id_from_short_name(short_name)
+            .unwrap();
+        checkpoint_db
+            .add_checkpoint(10_000, "", project_id)
+            .unwrap();
+        checkpoint_db
+    }
+
+    #[test]
+    fn interval_before_non_empty_first_checkpoint_overlaps() {
+        let checkpoint_db = database_with_checkpoint("p");
+        assert!(overlaps(&checkpoint_db, 1_000, 2_000));
+    }
+
+    #[test]
+    fn interval_before_empty_first_checkpoint_does_not_overlap() {
+        let checkpoint_db = database_with_checkpoint("");
+        assert!(!overlaps(&checkpoint_db, 1_000, 2_000));
+    }
+}
@@ -1,15 +1,23 @@
+mod exchange;
+mod export;
+mod import;
+mod migrate;
+
 use chrono::{
     ParseResult,
     {prelude::*, Duration},
 };
-use clap::{App, Arg, SubCommand};
+use clap::{App, AppSettings, Arg, SubCommand};
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 use std::{
     cmp::max,
+    collections::BTreeMap,
+    env,
     fs::{self, File},
-    io,
-    path::Path,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::Command,
 };
 use terminal_size::{terminal_size, Height, Width};
 use time_track::{CheckpointId, ProjectId};
@@ -21,6 +29,11 @@ const HM_FORMAT: &str = "%H:%M";
 const HMS_FORMAT: &str = "%H:%M:%S";
 const YMD_FORMAT: &str = "%Y-%m-%d";
 const YMDHM_FORMAT: &str = "%Y-%m-%d %H:%M";
+/// The time format of Timewarrior, always in UTC.
+const TIMEWARRIOR_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+/// The iCalendar format of times in a time zone, or in floating local time.
+const ICS_LOCAL_FORMAT: &str = "%Y%m%dT%H%M%S";
+const ICS_UTC_FORMAT: &str = "%Y%m%dT%H%M%SZ";
 
 #[cfg(debug_assertions)]
 const CONFIG_FILENAME: &str = "config_debug.json";
@@ -32,38 +45,93 @@ const DATABASE_FILENAME: &str = "database_debug.json";
 #[cfg(not(debug_assertions))]
 const DATABASE_FILENAME: &str = "database.json";
 
+#[cfg(debug_assertions)]
+const CACHE_FILENAME: &str = "cache_debug.json";
+#[cfg(not(debug_assertions))]
+const CACHE_FILENAME: &str = "cache.json";
+
+const GIT_HOOK_MARKER: &str = "# Installed by TimeTrack CLI";
+
 const QUALIFIER: &str = "com";
 const ORGANIZATION: &str = "Orsvarn";
 const APPLICATION: &str = "TimeTrack";
 
+/// What to do about the first checkpoint of each day, whose duration spans the night before.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum DayStart {
+    /// Count the first checkpoint of the day like any other.
+    Off,
+    /// Don't count the duration of the first checkpoint of the day.
+    Ignore,
+    /// Add an empty checkpoint at `work_start` before the first checkpoint of the day.
+    Insert,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
 struct Config {
     database_path: String,
+    /// How many minutes can pass without a checkpoint before `remind` warns about it.
+    remind_after: i64,
+    work_start: String,
+    work_end: String,
+    /// A shell command that `remind` runs instead of printing the reminder.
+    remind_command: Option<String>,
+    /// Maps the root directories of git repositories to the short names of projects.
+    git_projects: BTreeMap<String, String>,
+    /// How many hours a checkpoint can span before `log` and `check` point it out.
+    max_span_hours: i64,
+    day_start: DayStart,
+    /// Whether `rm` and `rm-project` ask before removing anything when run in a terminal.
+    confirm_destructive: bool,
+    /// Set by the `--dry-run` flag, so it's never saved.
+    #[serde(skip)]
+    dry_run: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        let proj_dirs = ProjectDirs::from(QUALIFIER, ORGANIZATION, APPLICATION);
+        let database_path = proj_dirs.data_dir().join(DATABASE_FILENAME);
+
+        Config {
+            database_path: database_path
+                .to_str()
+                .expect("Could not parse database path to string")
+                .to_string(),
+            remind_after: 60,
+            work_start: "09:00".to_string(),
+            work_end: "17:00".to_string(),
+            remind_command: None,
+            git_projects: BTreeMap::new(),
+            max_span_hours: 12,
+            day_start: DayStart::Off,
+            confirm_destructive: true,
+            dry_run: false,
+        }
+    }
 }
 
 impl Config {
-    fn read() -> io::Result<Config> {
+    /// Reads the config file, or writes a default one if there is none and it's not a dry run.
+    fn read(dry_run: bool) -> io::Result<Config> {
         let proj_dirs = ProjectDirs::from(QUALIFIER, ORGANIZATION, APPLICATION);
 
         let config_dir = proj_dirs.config_dir();
-        let data_dir = proj_dirs.data_dir();
-
         let config_path = config_dir.join(CONFIG_FILENAME);
-        let database_path = data_dir.join(DATABASE_FILENAME);
 
-        let config;
+        let mut config: Config;
         if config_path.is_file() {
             let file = File::open(config_path)?;
             config = serde_json::from_reader(file)?;
         } else {
-            config = Config {
-                database_path: database_path
-                    .to_str()
-                    .expect("Could not parse database path to string")
-                    .to_string(),
-            };
-            config.write()?;
+            config = Config::default();
+            if !dry_run {
+                config.write()?;
+            }
         }
+        config.dry_run = dry_run;
         Ok(config)
     }
 
@@ -82,11 +150,139 @@ impl Config {
     }
 }
 
+/// A summary of the database that is small enough to be read on every shell prompt.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Cache {
+    database_path: String,
+    last_timestamp: Option<i64>,
+    last_is_empty: bool,
+}
+
+impl Cache {
+    fn from_checkpoint_db(checkpoint_db: &time_track::CheckpointDb, config: &Config) -> Cache {
+        let last_checkpoint = checkpoint_db.get_log(&CheckpointId::Position(0));
+
+        Cache {
+            database_path: config.database_path.clone(),
+            last_timestamp: last_checkpoint
+                .as_ref()
+                .map(|log_checkpoint| log_checkpoint.timestamp),
+            last_is_empty: last_checkpoint.is_none_or(|log_checkpoint| {
+                log_checkpoint.checkpoint.project_id == ProjectId::NoId
+            }),
+        }
+    }
+
+    fn path() -> PathBuf {
+        let proj_dirs = ProjectDirs::from(QUALIFIER, ORGANIZATION, APPLICATION);
+        proj_dirs.cache_dir().join(CACHE_FILENAME)
+    }
+
+    /// Reads the cache, and rebuilds it from the database if it is missing or older than the
+    /// database file.
+    fn read(config: &Config) -> io::Result<Cache> {
+        let cache_path = Cache::path();
+
+        if let Some(cache) = Cache::read_if_fresh(&cache_path, config) {
+            return Ok(cache);
+        }
+
+        let checkpoint_db = read_database(Path::new(&config.database_path))?;
+        let cache = Cache::from_checkpoint_db(&checkpoint_db, config);
+        cache.write()?;
+        Ok(cache)
+    }
+
+    fn read_if_fresh(cache_path: &Path, config: &Config) -> Option<Cache> {
+        let cache_modified = fs::metadata(cache_path).ok()?.modified().ok()?;
+        let database_modified = fs::metadata(&config.database_path).ok()?.modified().ok()?;
+
+        // The database has been written without going through this program, for instance by
+        // editing it by hand.
+        if database_modified > cache_modified {
+            return None;
+        }
+
+        let file = File::open(cache_path).ok()?;
+        let cache: Cache = serde_json::from_reader(file).ok()?;
+
+        if cache.database_path == config.database_path {
+            Some(cache)
+        } else {
+            None
+        }
+    }
+
+    fn write(&self) -> io::Result<()> {
+        let cache_path = Cache::path();
+
+        if let Some(cache_dir) = cache_path.parent() {
+            if !cache_dir.exists() {
+                fs::create_dir_all(cache_dir)?;
+            }
+        }
+
+        let file = File::create(cache_path)?;
+        serde_json::to_writer(&file, self)?;
+        Ok(())
+    }
+}
+
+/// Reads the database, with an explanation instead of a parsing error if it was written by an old
+/// version of this program.
+fn read_database(path: &Path) -> io::Result<time_track::CheckpointDb> {
+    time_track::CheckpointDb::read(path).map_err(|e| {
+        if migrate::is_legacy_database(path) {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The database was written by version 0.2 or older, run `tt migrate` to convert it",
+            )
+        } else {
+            e
+        }
+    })
+}
+
+/// Returns the word for a change that was made, or for one that would have been made in a dry
+/// run, like "Added" or "Would add".
+fn change_verb<'a>(config: &Config, done: &'a str, dry_run: &'a str) -> &'a str {
+    if config.dry_run {
+        dry_run
+    } else {
+        done
+    }
+}
+
+/// Writes the database to disk and updates the cache to match it, unless it's a dry run.
+fn write_database(checkpoint_db: &time_track::CheckpointDb, config: &Config) -> io::Result<()> {
+    if config.dry_run {
+        println!("Dry run, the database was not changed");
+        return Ok(());
+    }
+
+    checkpoint_db.write(Path::new(&config.database_path))?;
+
+    // A cache that could not be written is rebuilt the next time it's read, since it will be
+    // older than the database, so this is not an error.
+    Cache::from_checkpoint_db(checkpoint_db, config)
+        .write()
+        .ok();
+
+    Ok(())
+}
+
 fn main() {
     let matches = App::new("TimeTrack CLI")
         .version(VERSION)
         .about("Track your time")
         .author("Lukas Orsvärn")
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .global(true)
+                .help("Show what a command would change without changing anything")
+                .takes_value(false),
+        )
         .subcommand(
             SubCommand::with_name("add")
                 .about("Adds a new time tracking checkpoint")
@@ -106,6 +302,29 @@ fn main() {
                         .short("t")
                         .help("The time and/or day to put the checkpoint at, the format is hh:mm or 'YYYY-MM-DD hh:mm'")
                         .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("before")
+                        .long("before")
+                        .help("Put the checkpoint before the checkpoint at this position, use with --offset or --time")
+                        .value_name("POSITION")
+                        .conflicts_with("after")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("after")
+                        .long("after")
+                        .help("Put the checkpoint after the checkpoint at this position, use with --offset or --time")
+                        .value_name("POSITION")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("offset")
+                        .long("offset")
+                        .short("o")
+                        .help("How long before or after the other checkpoint to put the checkpoint, like 30m, 2h or 1h30m")
+                        .conflicts_with("time")
+                        .takes_value(true),
                 ),
         )
         .subcommand(
@@ -125,6 +344,13 @@ fn main() {
                     Arg::with_name("position")
                         .help("The position of the checkpoint to remove")
                         .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("yes")
+                        .long("yes")
+                        .short("y")
+                        .help("Remove the checkpoint without asking")
+                        .takes_value(false),
                 ),
         )
         .subcommand(
@@ -168,6 +394,14 @@ fn main() {
                         .help("How much information to write out")
                         .short("v")
                         .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .help("How to format the output")
+                        .long("format")
+                        .possible_values(&["text", "markdown", "html"])
+                        .default_value("text")
+                        .takes_value(true),
                 ),
         )
         .subcommand(
@@ -212,6 +446,46 @@ fn main() {
                         .takes_value(false),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("split")
+                .about("Splits the span of a checkpoint by adding checkpoints inside it")
+                .arg(
+                    Arg::with_name("position")
+                        .help("The position in the list of the checkpoint to split (use log to find position)")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("points")
+                        .help("Where to add checkpoints, in the format 'TIME,PROJECT,MESSAGE'. TIME is hh:mm, 'YYYY-MM-DD hh:mm' or how far into the span, like 0.5 for the middle. PROJECT and MESSAGE can be left out")
+                        .required(true)
+                        .multiple(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("merge")
+                .about("Merges consecutive checkpoints into the most recent of them")
+                .arg(
+                    Arg::with_name("from")
+                        .help("The position of the first checkpoint to merge (use log to find position)")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .help("The position of the last checkpoint to merge")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("project")
+                        .long("project")
+                        .short("p")
+                        .help("The project of the merged checkpoint, needed when the checkpoints have different projects")
+                        .takes_value(true),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("projects")
                 .about("Lists all available projects")
@@ -244,6 +518,234 @@ fn main() {
                         .help("The short name of the project to remove")
                         .takes_value(true)
                         .required(true),
+                )
+                .arg(
+                    Arg::with_name("yes")
+                        .long("yes")
+                        .short("y")
+                        .help("Remove the project without asking")
+                        .takes_value(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("status")
+                .about("Shows the most recent checkpoint and how much time has been tracked today")
+                .arg(
+                    Arg::with_name("bar")
+                        .long("bar")
+                        .short("b")
+                        .help("Print a compact status block for a status bar")
+                        .possible_values(&["waybar", "i3blocks", "tmux"])
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("prompt")
+                .about("Prints a short indicator of the time since the last checkpoint for use in a shell prompt"),
+        )
+        .subcommand(
+            SubCommand::with_name("remind")
+                .about("Reminds you to add a checkpoint if none has been added for a while during working hours"),
+        )
+        .subcommand(
+            SubCommand::with_name("git-hook")
+                .about("Adds a checkpoint for every commit in a git repository")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("install")
+                        .about("Installs a post-commit hook in the current git repository")
+                        .arg(
+                            Arg::with_name("project")
+                                .help("The project to associate with commits in the repository")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("force")
+                                .long("force")
+                                .short("f")
+                                .help("Overwrite an existing post-commit hook")
+                                .takes_value(false),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("post-commit")
+                        .about("Adds a checkpoint for the latest commit, called by the hook")
+                        .setting(AppSettings::Hidden),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Exports checkpoints to formats that other tools understand")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(export_args(
+                    SubCommand::with_name("intervals")
+                        .about("Exports start and end times as JSON in the format of `timew export`"),
+                ))
+                .subcommand(export_args(
+                    SubCommand::with_name("timeclock")
+                        .about("Exports clock-in and clock-out lines for ledger and hledger")
+                        .arg(
+                            Arg::with_name("account-prefix")
+                                .long("account-prefix")
+                                .short("a")
+                                .help("Text to put before the project short names to form the account names, for instance 'time:'")
+                                .takes_value(true),
+                        ),
+                ))
+                .subcommand(export_args(
+                    SubCommand::with_name("ics")
+                        .about("Exports an iCalendar file with an event for each tracked span of time"),
+                ))
+                .subcommand(export_args(
+                    SubCommand::with_name("org")
+                        .about("Exports an Org-mode document with CLOCK lines grouped by project and message"),
+                ))
+                .subcommand(export_args(
+                    SubCommand::with_name("json")
+                        .about("Exports projects and checkpoints, all of them unless --start or --end is given, in a versioned JSON format that `import json` reads"),
+                )),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Imports time tracking data from other tools")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("timewarrior")
+                        .about("Imports Timewarrior data files, data directories or `timew export` output")
+                        .arg(
+                            Arg::with_name("file")
+                                .help("The files or directories to import")
+                                .takes_value(true)
+                                .multiple(true)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("map")
+                                .long("map")
+                                .short("m")
+                                .help("A JSON file mapping tags to project short names, unmapped tags are asked for")
+                                .value_name("FILE")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(csv_import_args(
+                    SubCommand::with_name("toggl")
+                        .about("Imports a detailed CSV export from Toggl"),
+                ))
+                .subcommand(csv_import_args(
+                    SubCommand::with_name("clockify")
+                        .about("Imports a detailed CSV export from Clockify"),
+                ))
+                .subcommand(
+                    SubCommand::with_name("watson")
+                        .about("Imports Watson's frames file")
+                        .arg(
+                            Arg::with_name("file")
+                                .help("The frames file, usually found in Watson's config directory, for instance ~/.config/watson/frames")
+                                .takes_value(true)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("map")
+                                .long("map")
+                                .short("m")
+                                .help("A JSON file mapping Watson projects to project short names, unmapped projects are asked for")
+                                .value_name("FILE")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("ics")
+                        .about("Imports events from an iCalendar file, recurring events are not expanded")
+                        .arg(
+                            Arg::with_name("file")
+                                .help("The .ics file to import")
+                                .takes_value(true)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("rules")
+                                .long("rules")
+                                .short("r")
+                                .help("A JSON file with rules that map event categories or summaries to project short names")
+                                .value_name("FILE")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("start")
+                                .help("What date to start from, defaults to today")
+                                .short("s")
+                                .long("start")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("end")
+                                .help("What date to end at, defaults to today")
+                                .short("e")
+                                .long("end")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("yes")
+                                .long("yes")
+                                .short("y")
+                                .help("Import events that match a rule without asking, and skip the rest")
+                                .takes_value(false),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("json")
+                        .about("Imports a file written by `export json`")
+                        .arg(
+                            Arg::with_name("file")
+                                .help("The JSON file to import")
+                                .takes_value(true)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("mode")
+                                .long("mode")
+                                .short("m")
+                                .help("Whether to add to the database or replace everything in it")
+                                .possible_values(&["merge", "replace"])
+                                .default_value("merge")
+                                .takes_value(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Checks the database for problems")
+                .arg(
+                    Arg::with_name("fix")
+                        .long("fix")
+                        .help("Make checkpoints with unknown projects empty, after showing how many hours stop being counted")
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("yes")
+                        .long("yes")
+                        .short("y")
+                        .help("Fix the problems without asking")
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("max-span")
+                        .long("max-span")
+                        .help("How many hours a checkpoint can span before it's reported, the default is set with `config --max-span`")
+                        .value_name("HOURS")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("migrate")
+                .about("Converts a database written by version 0.2 or older to the current format")
+                .arg(
+                    Arg::with_name("primary")
+                        .long("primary")
+                        .short("p")
+                        .help("Tag short names in order of priority, events with several tags get the first of these as project, the other tags are added to the message")
+                        .takes_value(true),
                 ),
         )
         .subcommand(
@@ -256,117 +758,552 @@ fn main() {
                         .help("Set the path of the database file")
                         .value_name("FILE")
                         .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("remind-after")
+                        .long("remind-after")
+                        .help("Set how many minutes can pass without a checkpoint before `remind` warns")
+                        .value_name("MINUTES")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("work-start")
+                        .long("work-start")
+                        .help("Set when the working hours start, the format is hh:mm")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("work-end")
+                        .long("work-end")
+                        .help("Set when the working hours end, the format is hh:mm")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("remind-command")
+                        .long("remind-command")
+                        .help("Set a shell command to run instead of printing reminders, an empty string removes it")
+                        .value_name("COMMAND")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("max-span")
+                        .long("max-span")
+                        .help("Set how many hours a checkpoint can span before `log` and `check` point it out")
+                        .value_name("HOURS")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("day-start")
+                        .long("day-start")
+                        .help("Set how the first checkpoint of each day is handled: counted like any other, ignored, or preceded by an empty checkpoint at the start of the working hours")
+                        .possible_values(&["off", "ignore", "insert"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("confirm-destructive")
+                        .long("confirm-destructive")
+                        .help("Set whether `rm` and `rm-project` ask before removing anything")
+                        .possible_values(&["true", "false"])
+                        .takes_value(true),
                 ),
         )
         .get_matches();
 
-    let cfg = Config::read().expect("Could not read config file");
+    let cfg = Config::read(matches.is_present("dry-run")).expect("Could not read config file");
+
+    if let Err(e) = run(&matches, &cfg) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
 
+/// Runs the subcommand that was given.
+fn run(matches: &clap::ArgMatches, cfg: &Config) -> io::Result<()> {
     if let Some(matches) = matches.subcommand_matches("add") {
-        add_checkpoint(matches, &cfg).expect("Failed adding checkpoint");
+        add_checkpoint(matches, cfg)?;
     }
     if let Some(matches) = matches.subcommand_matches("rm") {
-        remove_checkpoint(matches, &cfg).unwrap();
+        remove_checkpoint(matches, cfg)?;
     }
     if let Some(matches) = matches.subcommand_matches("print") {
-        print_checkpoint(matches, &cfg).unwrap();
+        print_checkpoint(matches, cfg)?;
     }
     if let Some(matches) = matches.subcommand_matches("log") {
-        log(matches, &cfg).unwrap();
+        log(matches, cfg)?;
     }
     if let Some(matches) = matches.subcommand_matches("edit") {
-        edit_checkpoint(matches, &cfg).unwrap();
+        edit_checkpoint(matches, cfg)?;
+    }
+    if let Some(matches) = matches.subcommand_matches("split") {
+        split_checkpoint(matches, cfg)?;
+    }
+    if let Some(matches) = matches.subcommand_matches("merge") {
+        merge_checkpoints(matches, cfg)?;
     }
     if let Some(_matches) = matches.subcommand_matches("projects") {
-        list_projects(&cfg).unwrap();
+        list_projects(cfg)?;
     }
     if let Some(matches) = matches.subcommand_matches("add-project") {
-        add_project(matches, &cfg).unwrap();
+        add_project(matches, cfg)?;
     }
     if let Some(matches) = matches.subcommand_matches("rm-project") {
-        remove_project(matches, &cfg).unwrap();
+        remove_project(matches, cfg)?;
+    }
+    if let Some(matches) = matches.subcommand_matches("status") {
+        status(matches, cfg)?;
+    }
+    if let Some(matches) = matches.subcommand_matches("prompt") {
+        prompt(matches, cfg)?;
+    }
+    if let Some(matches) = matches.subcommand_matches("remind") {
+        remind(matches, cfg)?;
+    }
+    if let Some(matches) = matches.subcommand_matches("git-hook") {
+        git_hook(matches, cfg)?;
+    }
+    if let Some(matches) = matches.subcommand_matches("export") {
+        export::export(matches, cfg)?;
+    }
+    if let Some(matches) = matches.subcommand_matches("import") {
+        import::import(matches, cfg)?;
+    }
+    if let Some(matches) = matches.subcommand_matches("check") {
+        check(matches, cfg)?;
+    }
+    if let Some(matches) = matches.subcommand_matches("migrate") {
+        migrate::migrate(matches, cfg)?;
     }
     if let Some(matches) = matches.subcommand_matches("config") {
-        config(matches, &cfg).unwrap();
+        config(matches, cfg)?;
     }
+
+    Ok(())
+}
+
+/// Adds the arguments that all export formats have.
+fn export_args<'a, 'b>(subcommand: App<'a, 'b>) -> App<'a, 'b> {
+    subcommand
+        .arg(
+            Arg::with_name("start")
+                .help("What date to start from, defaults to the first checkpoint")
+                .short("s")
+                .long("start")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("end")
+                .help("What date to end at, defaults to today")
+                .short("e")
+                .long("end")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("output")
+                .help("The file to write to, defaults to the terminal")
+                .short("o")
+                .long("output")
+                .value_name("FILE")
+                .takes_value(true),
+        )
+}
+
+/// Adds the arguments for importing CSV exports from other tools.
+fn csv_import_args<'a, 'b>(subcommand: App<'a, 'b>) -> App<'a, 'b> {
+    subcommand
+        .arg(
+            Arg::with_name("file")
+                .help("The CSV file to import")
+                .takes_value(true)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("date-format")
+                .long("date-format")
+                .help("The format of the dates in the file, for instance '%d/%m/%Y', detected by default")
+                .takes_value(true),
+        )
 }
 
 fn add_checkpoint(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
-    let timestamp = match matches.value_of("time") {
-        Some(t) => match parse_datetime(t, Local::today(), Local::now().time()) {
-            Ok(dt) => dt.timestamp(),
-            Err(e) => {
-                println!("Error parsing date/time: {:?}", e);
-                return Ok(());
-            }
-        },
-        None => Utc::now().timestamp(),
+    let timestamp = if matches.is_present("before") || matches.is_present("after") {
+        match relative_timestamp(matches, config)? {
+            Some(t) => t,
+            None => return Ok(()),
+        }
+    } else if matches.is_present("offset") {
+        println!("Use --before or --after to say what the offset is relative to");
+        return Ok(());
+    } else {
+        match matches.value_of("time") {
+            Some(t) => match parse_datetime(t, Local::today(), Local::now().time()) {
+                Ok(dt) => dt.timestamp(),
+                Err(e) => {
+                    println!("Error parsing date/time: {:?}", e);
+                    return Ok(());
+                }
+            },
+            None => Utc::now().timestamp(),
+        }
     };
 
     let message = matches.value_of("message").unwrap_or("");
     let short_name = matches.value_of("project").unwrap_or("");
-    let mut long_name = String::new();
-    let mut no_id = false;
 
-    let path = Path::new(&config.database_path);
-    let mut checkpoint_db = time_track::CheckpointDb::read(path)?;
+    add_checkpoint_at(timestamp, message, short_name, config)
+}
 
-    if let Some(project_id) = checkpoint_db.project_id_from_short_name(short_name) {
-        if let Some(project) = checkpoint_db.project_from_project_id(project_id) {
-            long_name = project.long_name.clone();
-        } else if let ProjectId::NoId = project_id {
-            no_id = true;
+/// Returns the time given by "offset" or "time" relative to the checkpoint given by "before" or
+/// "after", if it's between that checkpoint and its neighbour on the other side.
+fn relative_timestamp(matches: &clap::ArgMatches, config: &Config) -> io::Result<Option<i64>> {
+    let checkpoint_db = read_database(Path::new(&config.database_path))?;
+
+    let before = matches.is_present("before");
+    let position = match matches
+        .value_of("before")
+        .or_else(|| matches.value_of("after"))
+        .unwrap_or("")
+        .parse::<usize>()
+    {
+        Ok(p) => p,
+        _ => {
+            println!("Could not parse position value");
+            return Ok(None);
         }
+    };
 
-        checkpoint_db
-            .add_checkpoint(timestamp, message, project_id)
-            .unwrap();
-        checkpoint_db.write(path)?;
+    let reference = match CheckpointId::Position(position).to_timestamp(&checkpoint_db) {
+        Some(t) => t,
+        None => {
+            println!("Could not find an checkpoint at the given position");
+            return Ok(None);
+        }
+    };
+    let reference_time = Local.timestamp(reference, 0);
+
+    // Positions count from the most recent checkpoint, so the older neighbour has a higher one.
+    let neighbour = if before {
+        CheckpointId::Position(position + 1).to_timestamp(&checkpoint_db)
     } else {
-        println!(
-            "Failed to add checkpoint, project with short name does not exist: '{}'",
-            short_name
-        );
-        return Ok(());
-    }
+        position
+            .checked_sub(1)
+            .and_then(|p| CheckpointId::Position(p).to_timestamp(&checkpoint_db))
+    };
 
-    let duration_str = hour_string_from_i64(
-        checkpoint_db
-            .get_checkpoint_duration(&CheckpointId::Timestamp(timestamp))
-            .unwrap_or(0),
-    );
+    let timestamp = match (matches.value_of("offset"), matches.value_of("time")) {
+        (Some(offset), _) => match parse_offset(offset).and_then(|o| {
+            if before {
+                reference.checked_sub(o)
+            } else {
+                reference.checked_add(o)
+            }
+        }) {
+            Some(t) if NaiveDateTime::from_timestamp_opt(t, 0).is_some() => t,
+            Some(_) => {
+                println!("The offset '{}' is too large", offset);
+                return Ok(None);
+            }
+            None => {
+                println!(
+                    "Could not parse offset '{}', the format is like 30m, 2h or 1h30m",
+                    offset
+                );
+                return Ok(None);
+            }
+        },
+        (None, Some(t)) => match parse_datetime(t, reference_time.date(), reference_time.time()) {
+            Ok(dt) => dt.timestamp(),
+            Err(e) => {
+                println!("Error parsing date/time: {:?}", e);
+                return Ok(None);
+            }
+        },
+        (None, None) => {
+            println!("Use --offset or --time to say when to put the checkpoint");
+            return Ok(None);
+        }
+    };
 
-    let format_str = format!("{} {}", YMD_FORMAT, HM_FORMAT);
-    let time_str = Local.timestamp(timestamp, 0).format(&format_str);
-    let message = if message.is_empty() {
-        "No message".to_string()
+    let (earliest, latest) = if before {
+        (neighbour, Some(reference))
     } else {
-        format!("'{}'", message)
+        (Some(reference), neighbour)
     };
 
-    if no_id {
-        println!(
-            "Added empty checkpoint at '{time}' ({duration}h): {message}",
-            time = time_str,
-            duration = duration_str,
-            message = message,
-        );
-    } else {
+    if earliest.is_some_and(|t| timestamp <= t) || latest.is_some_and(|t| timestamp >= t) {
+        let format_time = |t: Option<i64>, default: &str| {
+            t.map_or(default.to_string(), |t| {
+                format!("'{}'", Local.timestamp(t, 0).format(YMDHM_FORMAT))
+            })
+        };
         println!(
-            "Added checkpoint for '{long}' at '{time}' ({duration}h): {message}",
-            time = time_str,
-            duration = duration_str,
-            message = message,
-            long = long_name
+            "The checkpoint would be at '{}', which is not between {} and {}",
+            Local.timestamp(timestamp, 0).format(YMDHM_FORMAT),
+            format_time(earliest, "the start"),
+            format_time(latest, "the end")
         );
+        return Ok(None);
+    }
+
+    Ok(Some(timestamp))
+}
+
+/// Parses a duration like "30m", "2h" or "1h30m" into seconds. A number without a unit is minutes.
+fn parse_offset(offset: &str) -> Option<i64> {
+    if let Ok(minutes) = offset.parse::<i64>() {
+        return minutes.checked_mul(60);
+    }
+
+    let mut seconds: i64 = 0;
+    let mut number = String::new();
+    for c in offset.chars() {
+        let unit = match c {
+            '0'..='9' => {
+                number.push(c);
+                continue;
+            }
+            'h' => 60 * 60,
+            'm' => 60,
+            _ => return None,
+        };
+        let part = number.parse::<i64>().ok()?.checked_mul(unit)?;
+        seconds = seconds.checked_add(part)?;
+        number.clear();
+    }
+
+    if number.is_empty() && !offset.is_empty() {
+        Some(seconds)
+    } else {
+        None
+    }
+}
+
+/// Returns the timestamp and the duration of the first checkpoint after the given time, which is
+/// the checkpoint whose span changes when a checkpoint is added, moved or removed there.
+fn next_checkpoint_duration(
+    checkpoint_db: &time_track::CheckpointDb,
+    timestamp: i64,
+) -> Option<(i64, Option<i64>)> {
+    let (next_timestamp, _) = checkpoint_db.checkpoints.range(timestamp + 1..).next()?;
+    Some((
+        *next_timestamp,
+        checkpoint_db.get_checkpoint_duration(&CheckpointId::Timestamp(*next_timestamp)),
+    ))
+}
+
+/// Returns a value for printing, or how it changed if it did, like "a -> b".
+fn change_string(before: &str, after: &str) -> String {
+    if before == after {
+        after.to_string()
+    } else {
+        format!("{} -> {}", before, after)
+    }
+}
+
+/// Returns a duration for printing, or how it changed if it did, like "1.5h -> 0.5h".
+fn duration_change_string(before: Option<i64>, after: Option<i64>) -> String {
+    let format_duration =
+        |d: Option<i64>| d.map_or("-".to_string(), |d| format!("{}h", hour_string_from_i64(d)));
+
+    change_string(&format_duration(before), &format_duration(after))
+}
+
+/// The parts of a checkpoint that are shown when it's edited or removed.
+struct CheckpointSummary {
+    time: String,
+    project: String,
+    message: String,
+    duration: Option<i64>,
+}
+
+impl CheckpointSummary {
+    fn new(checkpoint_db: &time_track::CheckpointDb, timestamp: i64) -> Option<CheckpointSummary> {
+        let checkpoint_id = CheckpointId::Timestamp(timestamp);
+        let checkpoint = checkpoint_db.get_checkpoint(&checkpoint_id)?;
+
+        Some(CheckpointSummary {
+            time: Local
+                .timestamp(timestamp, 0)
+                .format(YMDHM_FORMAT)
+                .to_string(),
+            project: checkpoint_db
+                .project_from_project_id(checkpoint.project_id)
+                .map_or(String::new(), |project| project.long_name.clone()),
+            message: checkpoint.message.clone(),
+            duration: checkpoint_db.get_checkpoint_duration(&checkpoint_id),
+        })
+    }
+
+    fn print(&self) {
+        self.print_changes_from(self);
+    }
+
+    /// Prints the checkpoint, with the parts that differ from how it was before shown as changes.
+    fn print_changes_from(&self, before: &CheckpointSummary) {
+        print_key_value("Time", &change_string(&before.time, &self.time));
+        print_key_value("Project", &change_string(&before.project, &self.project));
+        print_key_value("Message", &change_string(&before.message, &self.message));
+        print_key_value(
+            "Duration",
+            &duration_change_string(before.duration, self.duration),
+        );
+    }
+}
+
+/// Prints how the durations of the checkpoints after a changed checkpoint changed.
+fn print_next_changes(
+    checkpoint_db: &time_track::CheckpointDb,
+    next_durations: &[(i64, Option<i64>)],
+) {
+    for (timestamp, duration_before) in next_durations {
+        let duration_after =
+            checkpoint_db.get_checkpoint_duration(&CheckpointId::Timestamp(*timestamp));
+        print_key_value(
+            &format!(
+                "Next at {}",
+                Local.timestamp(*timestamp, 0).format(HM_FORMAT)
+            ),
+            &duration_change_string(*duration_before, duration_after),
+        );
+    }
+}
+
+/// Adds a checkpoint to the database and prints the result.
+fn add_checkpoint_at(
+    timestamp: i64,
+    message: &str,
+    short_name: &str,
+    config: &Config,
+) -> io::Result<()> {
+    let mut long_name = String::new();
+    let mut no_id = false;
+    let mut starts_day = false;
+    let next;
+
+    let path = Path::new(&config.database_path);
+    let mut checkpoint_db = read_database(path)?;
+
+    if let Some(project_id) = checkpoint_db.project_id_from_short_name(short_name) {
+        if let Some(project) = checkpoint_db.project_from_project_id(project_id) {
+            long_name = project.long_name.clone();
+        } else if let ProjectId::NoId = project_id {
+            no_id = true;
+        }
+
+        if !no_id && is_first_of_day(&checkpoint_db, timestamp) {
+            starts_day = true;
+
+            if config.day_start == DayStart::Insert {
+                if let Some(start) = work_start_of_day(timestamp, config) {
+                    checkpoint_db
+                        .add_checkpoint(start, "", ProjectId::NoId)
+                        .unwrap();
+                    starts_day = false;
+                    println!(
+                        "{} empty checkpoint at '{}' to start the day",
+                        change_verb(config, "Added", "Would add"),
+                        Local.timestamp(start, 0).format(YMDHM_FORMAT)
+                    );
+                }
+            }
+        }
+
+        // Adding a checkpoint shortens the span of the checkpoint after it.
+        next = next_checkpoint_duration(&checkpoint_db, timestamp);
+
+        checkpoint_db
+            .add_checkpoint(timestamp, message, project_id)
+            .unwrap();
+        write_database(&checkpoint_db, config)?;
+    } else {
+        println!(
+            "Failed to add checkpoint, project with short name does not exist: '{}'",
+            short_name
+        );
+        return Ok(());
+    }
+
+    let duration_str = if starts_day && config.day_start == DayStart::Ignore {
+        "not counted".to_string()
+    } else {
+        format!(
+            "{}h",
+            hour_string_from_i64(
+                checkpoint_db
+                    .get_checkpoint_duration(&CheckpointId::Timestamp(timestamp))
+                    .unwrap_or(0),
+            )
+        )
+    };
+
+    let format_str = format!("{} {}", YMD_FORMAT, HM_FORMAT);
+    let time_str = Local.timestamp(timestamp, 0).format(&format_str);
+    let message = if message.is_empty() {
+        "No message".to_string()
+    } else {
+        format!("'{}'", message)
+    };
+
+    if no_id {
+        println!(
+            "{verb} empty checkpoint at '{time}' ({duration}): {message}",
+            verb = change_verb(config, "Added", "Would add"),
+            time = time_str,
+            duration = duration_str,
+            message = message,
+        );
+    } else {
+        println!(
+            "{verb} checkpoint for '{long}' at '{time}' ({duration}): {message}",
+            verb = change_verb(config, "Added", "Would add"),
+            time = time_str,
+            duration = duration_str,
+            message = message,
+            long = long_name
+        );
+    }
+
+    if let Some((next_timestamp, duration_before)) = next {
+        let duration_after =
+            checkpoint_db.get_checkpoint_duration(&CheckpointId::Timestamp(next_timestamp));
+        println!(
+            "The checkpoint after it at '{}' {}: {}",
+            Local.timestamp(next_timestamp, 0).format(&format_str),
+            change_verb(config, "changed", "would change"),
+            duration_change_string(duration_before, duration_after)
+        );
+    }
+
+    if starts_day {
+        if config.day_start == DayStart::Ignore {
+            println!("This is the first checkpoint of the day, so its duration is not counted");
+        } else {
+            println!("Warning: this is the first checkpoint of the day and it has a project, so the time since the last checkpoint before today is counted. Add an empty checkpoint when you start working, or see `tt config --day-start`");
+        }
     }
 
     Ok(())
 }
 
+/// Returns the start of the working hours on the day of the timestamp, if it's before the
+/// timestamp.
+fn work_start_of_day(timestamp: i64, config: &Config) -> Option<i64> {
+    let work_start = NaiveTime::parse_from_str(&config.work_start, HM_FORMAT).ok()?;
+    let start = Local
+        .timestamp(timestamp, 0)
+        .date()
+        .and_time(work_start)?
+        .timestamp();
+
+    if start < timestamp {
+        Some(start)
+    } else {
+        None
+    }
+}
+
 fn remove_checkpoint(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
     let path = Path::new(&config.database_path);
-    let mut checkpoint_db = time_track::CheckpointDb::read(path)?;
+    let mut checkpoint_db = read_database(path)?;
 
     let checkpoint_id = match matches.value_of("position") {
         Some(position) => match position.parse::<usize>() {
@@ -379,20 +1316,40 @@ fn remove_checkpoint(matches: &clap::ArgMatches, config: &Config) -> io::Result<
         None => CheckpointId::Position(0),
     };
 
-    match checkpoint_db.remove_checkpoint(&checkpoint_id) {
-        Some(e) => {
-            checkpoint_db.write(path)?;
-            println!("Removed {:?}", e);
+    let timestamp = match checkpoint_id.to_timestamp(&checkpoint_db) {
+        Some(t) => t,
+        None => {
+            println!("Could not find an checkpoint at the given position");
+            return Ok(());
         }
-        None => println!("Could not find an checkpoint at the given position"),
     };
 
+    let next = next_checkpoint_duration(&checkpoint_db, timestamp);
+
+    println!("The checkpoint to remove:");
+    CheckpointSummary::new(&checkpoint_db, timestamp)
+        .unwrap()
+        .print();
+    if !confirm_destructive(matches, config, "Remove the checkpoint?")? {
+        println!("The checkpoint was not removed");
+        return Ok(());
+    }
+
+    checkpoint_db.remove_checkpoint(&CheckpointId::Timestamp(timestamp));
+    write_database(&checkpoint_db, config)?;
+
+    println!(
+        "{} the checkpoint",
+        change_verb(config, "Removed", "Would remove")
+    );
+    print_next_changes(&checkpoint_db, next.as_slice());
+
     Ok(())
 }
 
 fn print_checkpoint(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
     let path = Path::new(&config.database_path);
-    let checkpoint_db = time_track::CheckpointDb::read(path)?;
+    let checkpoint_db = read_database(path)?;
 
     let position = match matches.value_of("position") {
         Some(p) => match p.parse::<usize>() {
@@ -431,10 +1388,6 @@ fn print_checkpoint(matches: &clap::ArgMatches, config: &Config) -> io::Result<(
         None => "-".to_string(),
     };
 
-    fn print_key_value(key: &str, value: &str) {
-        println!("{:>15.15}: {}", key, value);
-    }
-
     print_key_value("Time", &time);
     print_key_value("Duration", &duration);
     print_key_value("Message", &log_checkpoint.checkpoint.message);
@@ -444,14 +1397,303 @@ fn print_checkpoint(matches: &clap::ArgMatches, config: &Config) -> io::Result<(
     Ok(())
 }
 
+fn print_key_value(key: &str, value: &str) {
+    println!("{:>15.15}: {}", key, value);
+}
+
+/// Prints the question and returns the answer the user types, without surrounding whitespace.
+fn prompt_line(question: &str) -> io::Result<String> {
+    print!("{}", question);
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
+
+/// Asks before a command removes something, unless `--yes` was given, it's turned off in the
+/// config, it's a dry run or there's nobody at a terminal to answer.
+fn confirm_destructive(
+    matches: &clap::ArgMatches,
+    config: &Config,
+    question: &str,
+) -> io::Result<bool> {
+    if matches.is_present("yes")
+        || !config.confirm_destructive
+        || config.dry_run
+        || !io::stdin().is_terminal()
+    {
+        return Ok(true);
+    }
+    confirm(question)
+}
+
+/// Asks a yes or no question, where no is the default.
+fn confirm(question: &str) -> io::Result<bool> {
+    let answer = prompt_line(&format!("{} [y/N] ", question))?;
+    Ok(answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes"))
+}
+
 fn hour_string_from_i64(x: i64) -> String {
     format!("{:.1}", x as f32 / 60. / 60.)
 }
 
+fn hm_string_from_i64(x: i64) -> String {
+    let sign = if x < 0 { "-" } else { "" };
+    let x = x.abs();
+    format!("{}{}:{:02}", sign, x / 60 / 60, x / 60 % 60)
+}
+
+/// Returns true if there is no checkpoint before the timestamp on the same day.
+fn is_first_of_day(checkpoint_db: &time_track::CheckpointDb, timestamp: i64) -> bool {
+    let day_start = Local
+        .timestamp(timestamp, 0)
+        .date()
+        .and_hms(0, 0, 0)
+        .timestamp();
+
+    checkpoint_db
+        .checkpoints
+        .range(day_start..timestamp)
+        .next()
+        .is_none()
+}
+
+/// Returns the duration of a checkpoint that counts as tracked time. Empty checkpoints don't
+/// count, and neither does the first checkpoint of the day when `day_start` is "ignore".
+fn counted_duration(
+    checkpoint_db: &time_track::CheckpointDb,
+    log_checkpoint: &time_track::LogCheckpoint,
+    config: &Config,
+) -> Option<i64> {
+    if log_checkpoint.checkpoint.project_id == ProjectId::NoId {
+        return None;
+    }
+    if config.day_start == DayStart::Ignore
+        && is_first_of_day(checkpoint_db, log_checkpoint.timestamp)
+    {
+        return None;
+    }
+    log_checkpoint.duration
+}
+
+/// Returns the summed counted duration of all checkpoints between the given times.
+fn tracked_duration_between_times(
+    checkpoint_db: &time_track::CheckpointDb,
+    start: &DateTime<Local>,
+    end: &DateTime<Local>,
+    config: &Config,
+) -> i64 {
+    checkpoint_db
+        .get_log_between_times(start, end)
+        .iter()
+        .filter_map(|log_checkpoint| counted_duration(checkpoint_db, log_checkpoint, config))
+        .sum()
+}
+
+fn print_table(pos: &str, duration: &str, time: &str, projects: &str, message: &str) {
+    let terminal_width: usize = match terminal_size() {
+        Some((Width(w), Height(_))) => w.into(),
+        None => DEFAULT_TERMINAL_WIDTH,
+    };
+
+    let head = format!(
+        "{:<6.6}|{:<5.5}|{:<6.6}|{:<16.16}|",
+        pos, duration, time, projects
+    );
+
+    let tail_length: usize =
+        max(terminal_width as i16 - head.chars().count() as i16 - 1, 4) as usize;
+
+    let output = format!("{}{:<width$.width$}", head, message, width = tail_length);
+
+    println!("{}", output.trim());
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LogFormat {
+    Text,
+    Markdown,
+    Html,
+}
+
+/// Escapes the characters that Markdown or inline HTML would interpret, so messages are shown as
+/// written in a table cell.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '|' | '<' | '>' | '*' | '_' | '`' | '[' | ']' | '#' | '&' | '~' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Prints the parts of a log as plain text for the terminal, or as a Markdown or HTML document.
+struct LogPrinter {
+    format: LogFormat,
+    table_open: bool,
+}
+
+impl LogPrinter {
+    fn new(format: LogFormat) -> LogPrinter {
+        LogPrinter {
+            format,
+            table_open: false,
+        }
+    }
+
+    fn title(&mut self, title: &str) {
+        let mut chars = title.chars();
+        let capitalized: String = chars
+            .next()
+            .map(|c| c.to_uppercase().chain(chars).collect())
+            .unwrap_or_default();
+
+        match self.format {
+            LogFormat::Text => println!("Printing {}", title),
+            LogFormat::Markdown => println!("# {}", escape_markdown(&capitalized)),
+            LogFormat::Html => {
+                let capitalized = escape_html(&capitalized);
+                println!("<!DOCTYPE html>");
+                println!("<html>");
+                println!("<head>");
+                println!("<meta charset=\"utf-8\">");
+                println!("<title>{}</title>", capitalized);
+                println!("<style>");
+                println!("body {{ font-family: sans-serif; }}");
+                println!("table {{ border-collapse: collapse; }}");
+                println!(
+                    "th, td {{ border: 1px solid #ccc; padding: 0.2em 0.5em; text-align: left; }}"
+                );
+                println!("</style>");
+                println!("</head>");
+                println!("<body>");
+                println!("<h1>{}</h1>", capitalized);
+            }
+        }
+    }
+
+    fn paragraph(&mut self, text: &str) {
+        match self.format {
+            LogFormat::Text => println!("{}", text),
+            LogFormat::Markdown => println!("\n{}", escape_markdown(text)),
+            LogFormat::Html => println!("<p>{}</p>", escape_html(text)),
+        }
+    }
+
+    fn day(&mut self, day: &str) {
+        self.close_table();
+        match self.format {
+            LogFormat::Text => println!("\n{}", day),
+            LogFormat::Markdown => println!("\n## {}", escape_markdown(day)),
+            LogFormat::Html => println!("<h2>{}</h2>", escape_html(day)),
+        }
+    }
+
+    /// Prints the header of the checkpoint table. Markdown and HTML tables get their header when
+    /// the first row of each table is printed instead.
+    fn table_header(&mut self) {
+        if self.format == LogFormat::Text {
+            print_table("Pos", "Dur", "Time", "Project", "Message");
+        }
+    }
+
+    fn row(&mut self, pos: &str, duration: &str, time: &str, project: &str, message: &str) {
+        match self.format {
+            LogFormat::Text => print_table(pos, duration, time, project, message),
+            LogFormat::Markdown => {
+                if !self.table_open {
+                    println!("\n| Pos | Dur | Time | Project | Message |");
+                    println!("|----:|----:|------|---------|---------|");
+                    self.table_open = true;
+                }
+                println!(
+                    "| {} | {} | {} | {} | {} |",
+                    pos,
+                    duration,
+                    time,
+                    escape_markdown(project),
+                    escape_markdown(message)
+                );
+            }
+            LogFormat::Html => {
+                if !self.table_open {
+                    println!("<table>");
+                    println!("<tr><th>Pos</th><th>Dur</th><th>Time</th><th>Project</th><th>Message</th></tr>");
+                    self.table_open = true;
+                }
+                println!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    pos,
+                    duration,
+                    time,
+                    escape_html(project),
+                    escape_html(message)
+                );
+            }
+        }
+    }
+
+    fn close_table(&mut self) {
+        if self.table_open && self.format == LogFormat::Html {
+            println!("</table>");
+        }
+        self.table_open = false;
+    }
+
+    fn day_duration(&mut self, duration: i64) {
+        self.close_table();
+        match self.format {
+            LogFormat::Text => println!("Duration: {}", hour_string_from_i64(duration)),
+            LogFormat::Markdown => {
+                println!("\n**Duration:** {}", hour_string_from_i64(duration))
+            }
+            LogFormat::Html => println!(
+                "<p><strong>Duration:</strong> {}</p>",
+                hour_string_from_i64(duration)
+            ),
+        }
+    }
+
+    fn total_duration(&mut self, duration: i64) {
+        self.close_table();
+        match self.format {
+            LogFormat::Text => {
+                println!("\nTotal duration: {}", hour_string_from_i64(duration));
+                println!("End");
+            }
+            LogFormat::Markdown => {
+                println!("\n**Total duration:** {}", hour_string_from_i64(duration))
+            }
+            LogFormat::Html => {
+                println!(
+                    "<p><strong>Total duration:</strong> {}</p>",
+                    hour_string_from_i64(duration)
+                );
+                println!("</body>");
+                println!("</html>");
+            }
+        }
+    }
+}
+
 /// Prints out checkpoints from the database in different ways.
 fn log(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
     let path = Path::new(&config.database_path);
-    let checkpoint_db = time_track::CheckpointDb::read(path)?;
+    let checkpoint_db = read_database(path)?;
 
     if (matches.is_present("range") || matches.is_present("back"))
         && (matches.is_present("start") || matches.is_present("end"))
@@ -517,47 +1759,24 @@ fn log(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
         v => v,
     };
 
-    // Can the `start.format` and `end.format` calls here be de-duplicated?
-    match verbosity {
-        1 => println!(
-            "Printing total stats for checkpoints between {} and {}",
-            start.format(YMDHM_FORMAT),
-            end.format(YMDHM_FORMAT)
-        ),
-        2 => println!(
-            "Printing daily stats for checkpoints between {} and {}",
-            start.format(YMDHM_FORMAT),
-            end.format(YMDHM_FORMAT)
-        ),
-        _ => println!(
-            "Printing checkpoints between {} and {}",
-            start.format(YMDHM_FORMAT),
-            end.format(YMDHM_FORMAT)
-        ),
-    }
-
-    fn print_table(pos: &str, duration: &str, time: &str, projects: &str, message: &str) {
-        let terminal_width: usize = match terminal_size() {
-            Some((Width(w), Height(_))) => w.into(),
-            None => DEFAULT_TERMINAL_WIDTH,
-        };
-
-        let head = format!(
-            "{:<6.6}|{:<5.5}|{:<6.6}|{:<16.16}|",
-            pos, duration, time, projects
-        );
-
-        let tail_length: usize =
-            max(terminal_width as i16 - head.chars().count() as i16 - 1, 4) as usize;
-
-        let output = format!("{}{:<width$.width$}", head, message, width = tail_length);
-
-        println!("{}", output.trim());
-    }
+    let format = match matches.value_of("format") {
+        Some("markdown") => LogFormat::Markdown,
+        Some("html") => LogFormat::Html,
+        _ => LogFormat::Text,
+    };
+    let mut printer = LogPrinter::new(format);
 
-    fn print_duration_today(d: i64) {
-        println!("Duration: {}", hour_string_from_i64(d));
-    }
+    let subject = match verbosity {
+        1 => "total stats for checkpoints",
+        2 => "daily stats for checkpoints",
+        _ => "checkpoints",
+    };
+    printer.title(&format!(
+        "{} between {} and {}",
+        subject,
+        start.format(YMDHM_FORMAT),
+        end.format(YMDHM_FORMAT)
+    ));
 
     let filter_projects = matches.value_of("filter").unwrap_or("");
     let filter_projects: Vec<_> = filter_projects.split_whitespace().collect();
@@ -571,17 +1790,16 @@ fn log(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
         .collect();
 
     if !filter_project_ids.is_empty() {
-        print!("Only including checkpoints with the following projects:");
-        for project in filter_projects {
-            print!(" {}", project);
-        }
-        println!();
+        printer.paragraph(&format!(
+            "Only including checkpoints with the following projects: {}",
+            filter_projects.join(" ")
+        ));
     }
 
     let mut current_date: Option<Date<Local>> = None;
 
     if verbosity >= 3 {
-        print_table("Pos", "Dur", "Time", "Project", "Message");
+        printer.table_header();
     }
 
     let log_checkpoints = checkpoint_db.get_log_between_times(&start, &end);
@@ -600,26 +1818,38 @@ fn log(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
         if current_date.is_none() || checkpoint_date != current_date.unwrap() {
             if current_date.is_some() {
                 if verbosity >= 2 {
-                    print_duration_today(daily_duration);
+                    printer.day_duration(daily_duration);
                 }
                 daily_duration = 0;
             }
 
             if verbosity >= 2 {
-                println!("\n{}", checkpoint_date.format("%Y-%m-%d %a"));
+                printer.day(&checkpoint_date.format("%Y-%m-%d %a").to_string());
+
+                if config.day_start != DayStart::Ignore
+                    && first_checkpoint_of_day(&checkpoint_db, checkpoint_date)
+                        .is_some_and(|checkpoint| checkpoint.project_id != ProjectId::NoId)
+                {
+                    printer.paragraph("Warning: the first checkpoint of the day is not empty, so the time since the day before is counted");
+                }
             }
             current_date = Some(checkpoint_date);
         }
 
-        let duration_string = match log_checkpoint.duration {
+        let mut flags = Vec::new();
+        let duration_string = match counted_duration(&checkpoint_db, log_checkpoint, config) {
             Some(d) => {
-                if log_checkpoint.checkpoint.project_id == ProjectId::NoId {
-                    "".to_string()
-                } else {
-                    total_duration += d;
-                    daily_duration += d;
-                    hour_string_from_i64(d)
+                total_duration += d;
+                daily_duration += d;
+
+                if Local.timestamp(log_checkpoint.timestamp - d, 0).date() != checkpoint_date {
+                    flags.push("[crosses midnight]".to_string());
+                }
+                if d > config.max_span_hours * 60 * 60 {
+                    flags.push(format!("[over {}h]", config.max_span_hours));
                 }
+
+                hour_string_from_i64(d)
             }
             None => "".to_string(),
         };
@@ -637,26 +1867,44 @@ fn log(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
             "".to_string()
         };
 
+        // Flags go before the message, so they aren't cut off in narrow terminals.
+        flags.push(log_checkpoint.checkpoint.message.clone());
+        let message_string = flags.join(" ");
+
         if verbosity >= 3 {
-            print_table(
+            printer.row(
                 &log_checkpoint.position.to_string(),
                 &duration_string,
                 &time_string,
                 &project_string,
-                &log_checkpoint.checkpoint.message,
+                message_string.trim_end(),
             );
         }
     }
 
     if verbosity >= 2 {
-        print_duration_today(daily_duration);
+        printer.day_duration(daily_duration);
     }
-    println!("\nTotal duration: {}", hour_string_from_i64(total_duration));
-    println!("End");
+    printer.total_duration(total_duration);
 
     Ok(())
 }
 
+/// Returns the earliest checkpoint on the given day.
+fn first_checkpoint_of_day(
+    checkpoint_db: &time_track::CheckpointDb,
+    date: Date<Local>,
+) -> Option<&time_track::Checkpoint> {
+    let start = date.and_hms(0, 0, 0).timestamp();
+    let end = date.and_hms(23, 59, 59).timestamp();
+
+    checkpoint_db
+        .checkpoints
+        .range(start..=end)
+        .next()
+        .map(|(_, checkpoint)| checkpoint)
+}
+
 fn parse_datetime(
     datetime_str: &str,
     default_date: Date<Local>,
@@ -694,36 +1942,319 @@ fn parse_datetime(
     }
 }
 
-fn edit_checkpoint(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
-    let path = Path::new(&config.database_path);
-    let mut checkpoint_db = time_track::CheckpointDb::read(path)?;
+/// Reads the "start" and "end" arguments, using the defaults for the ones that are missing. Prints
+/// an error and returns `None` if they can't be parsed.
+fn parse_time_range(
+    matches: &clap::ArgMatches,
+    default_start: DateTime<Local>,
+    default_end: DateTime<Local>,
+) -> Option<(DateTime<Local>, DateTime<Local>)> {
+    let start = match matches.value_of("start") {
+        Some(datetime_str) => match parse_datetime(
+            datetime_str,
+            Local::today(),
+            NaiveTime::from_hms(00, 00, 00),
+        ) {
+            Ok(dt) => dt,
+            Err(e) => {
+                println!("Error parsing \"start\" argument: {:?}", e);
+                return None;
+            }
+        },
+        None => default_start,
+    };
 
-    let checkpoint_id = match matches.value_of("position") {
-        Some(position) => match position.parse::<usize>() {
-            Ok(p) => CheckpointId::Position(p),
-            _ => {
-                println!("Could not parse position value");
-                return Ok(());
+    let end = match matches.value_of("end") {
+        Some(datetime_str) => match parse_datetime(
+            datetime_str,
+            Local::today(),
+            NaiveTime::from_hms(23, 59, 59),
+        ) {
+            Ok(dt) => dt,
+            Err(e) => {
+                println!("Error parsing \"end\" argument: {:?}", e);
+                return None;
             }
         },
-        None => CheckpointId::Position(0),
+        None => default_end,
     };
 
-    // By checking if the checkpoint_id exists in the databse here we can safely use `unwrap()`
-    // in the rest of the code with little risk of triggering a panic.
-    if !checkpoint_id.exists(&checkpoint_db) {
-        println!("Couldn't find an checkpoint at the given position");
+    Some((start, end))
+}
+
+/// Adds checkpoints inside the span of a checkpoint, for when it turns out that the time was spent
+/// on several things.
+fn split_checkpoint(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    let path = Path::new(&config.database_path);
+    let mut checkpoint_db = read_database(path)?;
+
+    let position = match matches.value_of("position").unwrap_or("").parse::<usize>() {
+        Ok(p) => p,
+        _ => {
+            println!("Could not parse position value");
+            return Ok(());
+        }
+    };
+
+    let (end, duration) = match checkpoint_db.get_log(&CheckpointId::Position(position)) {
+        Some(log_checkpoint) => (log_checkpoint.timestamp, log_checkpoint.duration),
+        None => {
+            println!("Could not find an checkpoint at the given position");
+            return Ok(());
+        }
+    };
+    let start = match duration {
+        Some(d) => end - d,
+        None => {
+            println!("Can't split the first checkpoint, it doesn't span any time");
+            return Ok(());
+        }
+    };
+
+    let end_time = Local.timestamp(end, 0);
+    let mut new_checkpoints = Vec::new();
+
+    for point in matches.values_of("points").unwrap() {
+        let mut parts = point.splitn(3, ',');
+        let at = parts.next().unwrap_or("").trim();
+        let short_name = parts.next().unwrap_or("").trim();
+        let message = parts.next().unwrap_or("").trim();
+
+        let timestamp = match at.parse::<f64>() {
+            Ok(fraction) => {
+                if !(fraction.is_finite() && 0.0 < fraction && fraction < 1.0) {
+                    println!(
+                        "'{}' is not a fraction of the span, it has to be between 0 and 1",
+                        at
+                    );
+                    return Ok(());
+                }
+                start + ((end - start) as f64 * fraction).round() as i64
+            }
+            Err(_) => match parse_datetime(at, end_time.date(), end_time.time()) {
+                Ok(dt) => dt.timestamp(),
+                Err(e) => {
+                    println!("Error parsing date/time '{}': {:?}", at, e);
+                    return Ok(());
+                }
+            },
+        };
+
+        if timestamp <= start || timestamp >= end {
+            println!(
+                "'{}' is not inside the span of the checkpoint, which is from '{}' to '{}'",
+                at,
+                Local.timestamp(start, 0).format(YMDHM_FORMAT),
+                end_time.format(YMDHM_FORMAT)
+            );
+            return Ok(());
+        }
+
+        let project_id = match checkpoint_db.project_id_from_short_name(short_name) {
+            Some(project_id) => project_id,
+            None => {
+                println!("Project with short name does not exist: '{}'", short_name);
+                return Ok(());
+            }
+        };
+
+        new_checkpoints.push((timestamp, message, project_id));
+    }
+
+    new_checkpoints.sort_by_key(|(timestamp, _, _)| *timestamp);
+    if new_checkpoints
+        .windows(2)
+        .any(|pair| pair[0].0 == pair[1].0)
+    {
+        println!("Can't add two checkpoints at the same time");
         return Ok(());
     }
 
-    let original_checkpoint = checkpoint_db
-        .get_checkpoint(&checkpoint_id)
+    for (timestamp, message, project_id) in &new_checkpoints {
+        checkpoint_db
+            .add_checkpoint(*timestamp, message, *project_id)
+            .unwrap();
+    }
+    write_database(&checkpoint_db, config)?;
+
+    println!(
+        "{} the checkpoint at '{}' ({}h) into:",
+        change_verb(config, "Split", "Would split"),
+        end_time.format(YMDHM_FORMAT),
+        hour_string_from_i64(end - start)
+    );
+    print_table("Pos", "Dur", "Time", "Project", "Message");
+
+    let timestamps = new_checkpoints.iter().map(|(timestamp, _, _)| *timestamp);
+    for timestamp in timestamps.chain(std::iter::once(end)) {
+        let log_checkpoint = checkpoint_db
+            .get_log(&CheckpointId::Timestamp(timestamp))
+            .unwrap();
+        let project = checkpoint_db
+            .project_from_project_id(log_checkpoint.checkpoint.project_id)
+            .map_or(String::new(), |project| project.long_name.clone());
+
+        print_table(
+            &log_checkpoint.position.to_string(),
+            &hour_string_from_i64(log_checkpoint.duration.unwrap_or(0)),
+            &Local.timestamp(timestamp, 0).format(HM_FORMAT).to_string(),
+            &project,
+            &log_checkpoint.checkpoint.message,
+        );
+    }
+
+    Ok(())
+}
+
+/// Replaces a range of consecutive checkpoints with one checkpoint at the time of the most recent
+/// of them, which spans the time of all of them.
+fn merge_checkpoints(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    let path = Path::new(&config.database_path);
+    let mut checkpoint_db = read_database(path)?;
+
+    let mut positions = Vec::new();
+    for arg in ["from", "to"] {
+        match matches.value_of(arg).unwrap_or("").parse::<usize>() {
+            Ok(p) => positions.push(p),
+            Err(e) => {
+                println!("Could not parse \"{}\" value: {}", arg, e);
+                return Ok(());
+            }
+        }
+    }
+    let (newest, oldest) = (
+        *positions.iter().min().unwrap(),
+        *positions.iter().max().unwrap(),
+    );
+
+    if newest == oldest {
+        println!("Need at least two checkpoints to merge");
+        return Ok(());
+    }
+
+    // Oldest first, so the messages are joined in the order they were written.
+    let mut timestamps = Vec::new();
+    for position in (newest..=oldest).rev() {
+        match CheckpointId::Position(position).to_timestamp(&checkpoint_db) {
+            Some(timestamp) => timestamps.push(timestamp),
+            None => {
+                println!("Could not find an checkpoint at position {}", position);
+                return Ok(());
+            }
+        }
+    }
+
+    let checkpoints: Vec<&time_track::Checkpoint> = timestamps
+        .iter()
+        .map(|timestamp| {
+            checkpoint_db
+                .get_checkpoint(&CheckpointId::Timestamp(*timestamp))
+                .unwrap()
+        })
+        .collect();
+
+    let project_id = match matches.value_of("project") {
+        Some(short_name) => match checkpoint_db.project_id_from_short_name(short_name) {
+            Some(project_id) => project_id,
+            None => {
+                println!("Invalid project short name: '{}'", short_name);
+                return Ok(());
+            }
+        },
+        None => {
+            let project_id = checkpoints[0].project_id;
+            if checkpoints
+                .iter()
+                .any(|checkpoint| checkpoint.project_id != project_id)
+            {
+                println!("The checkpoints have different projects, use --project to choose the project of the merged checkpoint");
+                return Ok(());
+            }
+            project_id
+        }
+    };
+
+    let message = checkpoints
+        .iter()
+        .map(|checkpoint| checkpoint.message.as_str())
+        .filter(|message| !message.is_empty())
+        .collect::<Vec<&str>>()
+        .join("; ");
+
+    let (latest, merged) = timestamps.split_last().unwrap();
+    let checkpoint_id = CheckpointId::Timestamp(*latest);
+    for timestamp in merged {
+        checkpoint_db.remove_checkpoint(&CheckpointId::Timestamp(*timestamp));
+    }
+    checkpoint_db
+        .get_checkpoint_mut(&checkpoint_id)
         .unwrap()
-        .clone();
+        .message = message;
+    if checkpoint_db
+        .set_checkpoint_project(checkpoint_id, project_id)
+        .is_err()
+    {
+        println!("Couldn't set the checkpoint project");
+        return Ok(());
+    }
+    write_database(&checkpoint_db, config)?;
+
+    let log_checkpoint = checkpoint_db.get_log(&checkpoint_id).unwrap();
+    let project = checkpoint_db
+        .project_from_project_id(log_checkpoint.checkpoint.project_id)
+        .map_or(String::new(), |project| project.long_name.clone());
+
+    println!(
+        "{} {} checkpoints into:",
+        change_verb(config, "Merged", "Would merge"),
+        timestamps.len()
+    );
+    print_table("Pos", "Dur", "Time", "Project", "Message");
+    print_table(
+        &log_checkpoint.position.to_string(),
+        &hour_string_from_i64(log_checkpoint.duration.unwrap_or(0)),
+        &Local.timestamp(*latest, 0).format(HM_FORMAT).to_string(),
+        &project,
+        &log_checkpoint.checkpoint.message,
+    );
+
+    Ok(())
+}
+
+fn edit_checkpoint(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    let path = Path::new(&config.database_path);
+    let mut checkpoint_db = read_database(path)?;
+
+    let checkpoint_id = match matches.value_of("position") {
+        Some(position) => match position.parse::<usize>() {
+            Ok(p) => CheckpointId::Position(p),
+            _ => {
+                println!("Could not parse position value");
+                return Ok(());
+            }
+        },
+        None => CheckpointId::Position(0),
+    };
+
+    // By checking if the checkpoint_id exists in the databse here we can safely use `unwrap()`
+    // in the rest of the code with little risk of triggering a panic.
+    if !checkpoint_id.exists(&checkpoint_db) {
+        println!("Couldn't find an checkpoint at the given position");
+        return Ok(());
+    }
+
+    // Positions change when the time is edited, so the checkpoint is referred to by its time.
+    let mut timestamp = checkpoint_id.to_timestamp(&checkpoint_db).unwrap();
+    let mut checkpoint_id = CheckpointId::Timestamp(timestamp);
+
+    let original = CheckpointSummary::new(&checkpoint_db, timestamp).unwrap();
+    let mut next_durations: Vec<(i64, Option<i64>)> =
+        next_checkpoint_duration(&checkpoint_db, timestamp)
+            .into_iter()
+            .collect();
 
     if let Some(date_time_str) = matches.value_of("time") {
-        let checkpoint_time =
-            Local.timestamp(checkpoint_id.to_timestamp(&checkpoint_db).unwrap(), 0);
+        let checkpoint_time = Local.timestamp(timestamp, 0);
         let date_time = parse_datetime(
             date_time_str,
             checkpoint_time.date(),
@@ -731,9 +2262,17 @@ fn edit_checkpoint(matches: &clap::ArgMatches, config: &Config) -> io::Result<()
         )
         .unwrap();
         let checkpoint = checkpoint_db.remove_checkpoint(&checkpoint_id).unwrap();
-        checkpoint_db
-            .checkpoints
-            .insert(date_time.timestamp(), checkpoint);
+
+        // The checkpoint after the new time gets a shorter span too.
+        if let Some(next) = next_checkpoint_duration(&checkpoint_db, date_time.timestamp()) {
+            if !next_durations.iter().any(|(t, _)| *t == next.0) {
+                next_durations.push(next);
+            }
+        }
+
+        timestamp = date_time.timestamp();
+        checkpoint_id = CheckpointId::Timestamp(timestamp);
+        checkpoint_db.checkpoints.insert(timestamp, checkpoint);
     }
 
     // Message
@@ -788,18 +2327,22 @@ fn edit_checkpoint(matches: &clap::ArgMatches, config: &Config) -> io::Result<()
         return Ok(());
     }
 
-    let edited_checkpoint = checkpoint_db.get_checkpoint(&checkpoint_id);
+    write_database(&checkpoint_db, config)?;
 
-    checkpoint_db.write(path)?;
-    println!("Sucessfully edited the checkpoint");
-    println!("Original: {:?}", original_checkpoint);
-    println!("  Edited: {:?}", edited_checkpoint);
+    println!(
+        "{} the checkpoint:",
+        change_verb(config, "Edited", "Would edit")
+    );
+    CheckpointSummary::new(&checkpoint_db, timestamp)
+        .unwrap()
+        .print_changes_from(&original);
+    print_next_changes(&checkpoint_db, &next_durations);
     Ok(())
 }
 
 fn list_projects(config: &Config) -> io::Result<()> {
     let path = Path::new(&config.database_path);
-    let checkpoint_db = time_track::CheckpointDb::read(path)?;
+    let checkpoint_db = read_database(path)?;
 
     println!("Projects:");
     for (id, project) in checkpoint_db.projects.iter() {
@@ -811,7 +2354,7 @@ fn list_projects(config: &Config) -> io::Result<()> {
 
 fn add_project(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
     let path = Path::new(&config.database_path);
-    let mut checkpoint_db = time_track::CheckpointDb::read(path)?;
+    let mut checkpoint_db = read_database(path)?;
 
     // I can unwrap these because they are required in Clap.
     let long_name = matches.value_of("long").unwrap();
@@ -829,10 +2372,11 @@ fn add_project(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
         }
     };
 
-    checkpoint_db.write(path)?;
+    write_database(&checkpoint_db, config)?;
 
     println!(
-        "Added project '{long}' (ID: '{id}', short name: '{short}')",
+        "{verb} project '{long}' (ID: '{id}', short name: '{short}')",
+        verb = change_verb(config, "Added", "Would add"),
         id = id,
         short = short_name,
         long = long_name,
@@ -843,16 +2387,564 @@ fn add_project(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
 
 fn remove_project(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
     let path = Path::new(&config.database_path);
-    let mut checkpoint_db = time_track::CheckpointDb::read(path)?;
+    let mut checkpoint_db = read_database(path)?;
 
     if let Some(short_name) = matches.value_of("short") {
         if let Some(project_id) = checkpoint_db.project_id_from_short_name(short_name) {
+            let long_name = checkpoint_db
+                .project_from_project_id(project_id)
+                .map_or(String::new(), |project| project.long_name.clone());
+            let used_by = checkpoint_db
+                .checkpoints
+                .values()
+                .filter(|checkpoint| checkpoint.project_id == project_id)
+                .count();
+
+            println!(
+                "The project to remove: '{}' ({}), used by {} checkpoints",
+                long_name, short_name, used_by
+            );
+            if !confirm_destructive(matches, config, "Remove the project?")? {
+                println!("The project was not removed");
+                return Ok(());
+            }
+
             checkpoint_db.remove_project(project_id).unwrap();
-            checkpoint_db.write(path)?;
+            write_database(&checkpoint_db, config)?;
+            println!(
+                "{} the project '{}'",
+                change_verb(config, "Removed", "Would remove"),
+                short_name
+            );
+        } else {
+            println!("Project with short name does not exist: '{}'", short_name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the most recent checkpoint, the time since it and today's tracked time.
+fn status(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    let path = Path::new(&config.database_path);
+    let checkpoint_db = read_database(path)?;
+
+    let now = Local::now();
+    let today = tracked_duration_between_times(
+        &checkpoint_db,
+        &Local::today().and_hms(00, 00, 00),
+        &Local::today().and_hms(23, 59, 59),
+        config,
+    );
+
+    if let Some(bar) = matches.value_of("bar") {
+        let week_start =
+            Local::today() - Duration::days(Local::today().weekday().num_days_from_monday().into());
+        let week = tracked_duration_between_times(
+            &checkpoint_db,
+            &week_start.and_hms(00, 00, 00),
+            &Local::today().and_hms(23, 59, 59),
+            config,
+        );
+        let last_checkpoint = checkpoint_db.get_log(&CheckpointId::Position(0));
+        let elapsed = last_checkpoint
+            .as_ref()
+            .map(|log_checkpoint| now.timestamp() - log_checkpoint.timestamp);
+        let on_the_clock = last_checkpoint
+            .is_some_and(|log_checkpoint| log_checkpoint.checkpoint.project_id != ProjectId::NoId);
+
+        print_status_bar(bar, elapsed, today, week, on_the_clock);
+        return Ok(());
+    }
+
+    let log_checkpoint = match checkpoint_db.get_log(&CheckpointId::Position(0)) {
+        Some(e) => e,
+        None => {
+            println!("There are no checkpoints in the database");
+            return Ok(());
+        }
+    };
+
+    let elapsed = now.timestamp() - log_checkpoint.timestamp;
+
+    let project = if let Some(project) =
+        checkpoint_db.project_from_project_id(log_checkpoint.checkpoint.project_id)
+    {
+        project.long_name.clone()
+    } else {
+        "".to_string()
+    };
+
+    let on_the_clock = if log_checkpoint.checkpoint.project_id == ProjectId::NoId {
+        "No, the last checkpoint is empty"
+    } else {
+        "Yes"
+    };
+
+    print_key_value(
+        "Last checkpoint",
+        &Local
+            .timestamp(log_checkpoint.timestamp, 0)
+            .format(YMDHM_FORMAT)
+            .to_string(),
+    );
+    print_key_value("Project", &project);
+    print_key_value("Message", &log_checkpoint.checkpoint.message);
+    print_key_value(
+        "Since",
+        &format!(
+            "{} ({}h)",
+            hm_string_from_i64(elapsed),
+            hour_string_from_i64(elapsed)
+        ),
+    );
+    print_key_value("Today", &format!("{}h", hour_string_from_i64(today)));
+    print_key_value("On the clock", on_the_clock);
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct WaybarStatus {
+    text: String,
+    tooltip: String,
+    class: String,
+}
+
+/// Prints a single status block in a format a status bar can poll.
+fn print_status_bar(bar: &str, elapsed: Option<i64>, today: i64, week: i64, on_the_clock: bool) {
+    let elapsed = match elapsed {
+        Some(e) => hm_string_from_i64(e),
+        None => "-".to_string(),
+    };
+    let today = format!("{}h", hour_string_from_i64(today));
+    let week = format!("{}h", hour_string_from_i64(week));
+
+    match bar {
+        "waybar" => {
+            let status = WaybarStatus {
+                text: format!("{} | {}", elapsed, today),
+                tooltip: format!(
+                    "Since last checkpoint: {}\nToday: {}\nThis week: {}",
+                    elapsed, today, week
+                ),
+                class: if on_the_clock { "on" } else { "off" }.to_string(),
+            };
+            println!(
+                "{}",
+                serde_json::to_string(&status).expect("Could not serialize status")
+            );
+        }
+        "i3blocks" => {
+            // i3blocks reads the full text, the short text and the color from separate lines.
+            println!("{} | {} | {}", elapsed, today, week);
+            println!("{}", elapsed);
+            if !on_the_clock {
+                println!("#888888");
+            }
+        }
+        _ => println!("{} | today {} | week {}", elapsed, today, week),
+    }
+}
+
+/// Prints the time since the last checkpoint, using the cache to avoid reading the whole database.
+fn prompt(_matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    let cache = Cache::read(config)?;
+
+    if let Some(timestamp) = cache.last_timestamp {
+        let elapsed = hm_string_from_i64(Utc::now().timestamp() - timestamp);
+        if cache.last_is_empty {
+            println!("⏱ ({})", elapsed);
+        } else {
+            println!("⏱ {}", elapsed);
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the database for problems that hand edits and forgotten checkpoints can cause, and fixes
+/// the ones that can be fixed without losing information.
+fn check(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    let path = Path::new(&config.database_path);
+    let mut checkpoint_db = read_database(path)?;
+
+    let max_span = match matches.value_of("max-span") {
+        Some(hours) => match hours.parse::<f64>() {
+            Ok(h) => (h * 60. * 60.) as i64,
+            Err(e) => {
+                println!("Error when parsing \"max-span\" argument: {:?}", e);
+                return Ok(());
+            }
+        },
+        None => config.max_span_hours * 60 * 60,
+    };
+
+    let mut problems = 0;
+
+    let mut short_names: BTreeMap<&str, usize> = BTreeMap::new();
+    for project in checkpoint_db.projects.values() {
+        *short_names.entry(&project.short_name).or_default() += 1;
+    }
+    for (short_name, count) in short_names {
+        if count > 1 {
+            println!("{} projects have the short name '{}'", count, short_name);
+            problems += 1;
+        }
+    }
+
+    let now = Utc::now().timestamp();
+    let mut unknown_projects = Vec::new();
+    // The tracked time of checkpoints with unknown projects, which isn't counted once they're empty.
+    let mut unknown_projects_duration = 0;
+    let mut previous_date: Option<Date<Local>> = None;
+    let mut previous_timestamp: Option<i64> = None;
+
+    // Positions count from the most recent checkpoint, and the checkpoints are iterated from the
+    // oldest, so they are worked out from the index instead of looked up for every checkpoint.
+    let count = checkpoint_db.checkpoints.len();
+    for (i, (&timestamp, checkpoint)) in checkpoint_db.checkpoints.iter().enumerate() {
+        let time = Local.timestamp(timestamp, 0);
+        let position = count - 1 - i;
+        let label = format!("Checkpoint {} at '{}'", position, time.format(YMDHM_FORMAT));
+        let is_empty = checkpoint.project_id == ProjectId::NoId;
+        let is_first_of_day = previous_date != Some(time.date());
+        let duration = previous_timestamp.map(|previous| timestamp - previous);
+
+        if !is_empty
+            && checkpoint_db
+                .project_from_project_id(checkpoint.project_id)
+                .is_none()
+        {
+            println!("{} refers to a project that does not exist", label);
+            unknown_projects.push(timestamp);
+            if !(is_first_of_day && config.day_start == DayStart::Ignore) {
+                unknown_projects_duration += duration.unwrap_or(0);
+            }
+            problems += 1;
+        }
+
+        if timestamp > now {
+            println!("{} is in the future", label);
+            problems += 1;
+        }
+
+        if let Some(duration) = duration {
+            if !is_empty && duration > max_span {
+                println!("{} spans {}h", label, hour_string_from_i64(duration));
+                problems += 1;
+            }
+        }
+        previous_timestamp = Some(timestamp);
+
+        if is_first_of_day {
+            if !is_empty && config.day_start != DayStart::Ignore {
+                println!(
+                    "{} is the first checkpoint of the day but is not empty",
+                    label
+                );
+                problems += 1;
+            }
+            previous_date = Some(time.date());
+        }
+    }
+
+    if problems == 0 {
+        println!("No problems found");
+        return Ok(());
+    }
+    println!("Found {} problems", problems);
+
+    if matches.is_present("fix") && !unknown_projects.is_empty() {
+        println!(
+            "Making the {} checkpoints with unknown projects empty stops counting {}h of tracked time",
+            unknown_projects.len(),
+            hour_string_from_i64(unknown_projects_duration)
+        );
+        if !confirm_destructive(matches, config, "Make them empty?")? {
+            return Ok(());
+        }
+
+        for timestamp in &unknown_projects {
+            let checkpoint_id = CheckpointId::Timestamp(*timestamp);
+            let checkpoint = checkpoint_db.get_checkpoint_mut(&checkpoint_id).unwrap();
+
+            // Keep the ID in the message, so the project can be restored by hand.
+            let note = format!("[unknown project {:?}]", checkpoint.project_id);
+            if !checkpoint.message.is_empty() {
+                checkpoint.message.push(' ');
+            }
+            checkpoint.message.push_str(&note);
+
+            if checkpoint_db
+                .set_checkpoint_project(checkpoint_id, ProjectId::NoId)
+                .is_err()
+            {
+                println!("Couldn't remove the checkpoint project");
+                return Ok(());
+            }
+        }
+
+        write_database(&checkpoint_db, config)?;
+        println!(
+            "{} {} checkpoints with unknown projects empty, the other problems have to be fixed by hand",
+            change_verb(config, "Made", "Would make"),
+            unknown_projects.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns a `Command` that runs the given command line in the platform's shell.
+fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C").arg(command);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c").arg(command);
+        shell
+    }
+}
+
+/// Warns if no checkpoint has been added for a while during working hours. Meant to be run
+/// periodically, for instance by cron.
+fn remind(_matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    let (work_start, work_end) = match (
+        NaiveTime::parse_from_str(&config.work_start, HM_FORMAT),
+        NaiveTime::parse_from_str(&config.work_end, HM_FORMAT),
+    ) {
+        (Ok(start), Ok(end)) => (start, end),
+        _ => {
+            println!(
+                "Could not parse the working hours in the config file: '{}' - '{}'",
+                config.work_start, config.work_end
+            );
+            return Ok(());
+        }
+    };
+
+    let now = Local::now();
+    if now.time() < work_start || now.time() >= work_end {
+        return Ok(());
+    }
+
+    let elapsed = match Cache::read(config)?.last_timestamp {
+        Some(timestamp) => now.timestamp() - timestamp,
+        None => return Ok(()),
+    };
+
+    if elapsed < config.remind_after * 60 {
+        return Ok(());
+    }
+
+    let message = format!(
+        "No checkpoint has been added for {}, write `tt add` to add one",
+        hm_string_from_i64(elapsed)
+    );
+
+    match &config.remind_command {
+        Some(command) => {
+            let status = shell_command(command)
+                .env("TT_MESSAGE", &message)
+                .env("TT_ELAPSED", elapsed.to_string())
+                .status()?;
+            if !status.success() {
+                println!("The remind command failed: {}", status);
+            }
+        }
+        None => println!("{}", message),
+    }
+
+    Ok(())
+}
+
+/// Runs git with the given arguments and returns its trimmed output, or `None` if git failed.
+fn git_output(args: &[&str]) -> io::Result<Option<String>> {
+    let output = Command::new("git").args(args).output()?;
+
+    if output.status.success() {
+        Ok(Some(
+            String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        ))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Quotes a string for a POSIX shell, so that no characters in it are interpreted.
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn git_hook(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    if let Some(matches) = matches.subcommand_matches("install") {
+        install_git_hook(matches, config)?;
+    }
+    if let Some(_matches) = matches.subcommand_matches("post-commit") {
+        git_post_commit(config)?;
+    }
+
+    Ok(())
+}
+
+fn install_git_hook(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    let top_level = match git_output(&["rev-parse", "--show-toplevel"])? {
+        Some(t) => t,
+        None => {
+            println!("The current directory is not in a git repository");
+            return Ok(());
+        }
+    };
+    let hook_path = match git_output(&["rev-parse", "--git-path", "hooks/post-commit"])? {
+        Some(h) => PathBuf::from(h),
+        None => {
+            println!("Could not find the hooks directory of the repository");
+            return Ok(());
+        }
+    };
+
+    if hook_path.is_file()
+        && !fs::read_to_string(&hook_path)?.contains(GIT_HOOK_MARKER)
+        && !matches.is_present("force")
+    {
+        println!(
+            "There already is a post-commit hook at '{}', use `--force` to overwrite it",
+            hook_path.display()
+        );
+        return Ok(());
+    }
+
+    if let Some(short_name) = matches.value_of("project") {
+        let checkpoint_db = read_database(Path::new(&config.database_path))?;
+        if checkpoint_db
+            .project_id_from_short_name(short_name)
+            .is_some()
+        {
+            let mut config_new = config.clone();
+            config_new
+                .git_projects
+                .insert(top_level.clone(), short_name.to_string());
+            if !config.dry_run {
+                config_new.write()?;
+            }
         } else {
             println!("Project with short name does not exist: '{}'", short_name);
+            return Ok(());
+        }
+    }
+
+    let executable = env::current_exe()?;
+    let hook = format!(
+        "#!/bin/sh\n{marker}\n{executable} git-hook post-commit\n",
+        marker = GIT_HOOK_MARKER,
+        executable = shell_quote(&executable.display().to_string()),
+    );
+
+    if config.dry_run {
+        println!(
+            "Would install post-commit hook at '{}'",
+            hook_path.display()
+        );
+    } else {
+        if let Some(hooks_dir) = hook_path.parent() {
+            if !hooks_dir.exists() {
+                fs::create_dir_all(hooks_dir)?;
+            }
+        }
+        fs::write(&hook_path, hook)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&hook_path, fs::Permissions::from_mode(0o755))?;
+        }
+
+        println!("Installed post-commit hook at '{}'", hook_path.display());
+    }
+    let short_name = matches
+        .value_of("project")
+        .or_else(|| config.git_projects.get(&top_level).map(String::as_str));
+    match short_name {
+        Some(short_name) => println!("Commits will be added to project '{}'", short_name),
+        None => println!(
+            "The repository is not mapped to a project, run `tt git-hook install <project>` to map it"
+        ),
+    }
+
+    Ok(())
+}
+
+/// Adds a checkpoint with the subject of the latest commit as message, for the project the
+/// repository is mapped to.
+fn git_post_commit(config: &Config) -> io::Result<()> {
+    // Rebases run the post-commit hook for every commit they rewrite.
+    for rebase_dir in &["rebase-merge", "rebase-apply"] {
+        if let Some(path) = git_output(&["rev-parse", "--git-path", rebase_dir])? {
+            if Path::new(&path).exists() {
+                return Ok(());
+            }
+        }
+    }
+
+    let (top_level, subject) = match (
+        git_output(&["rev-parse", "--show-toplevel"])?,
+        git_output(&["log", "-1", "--pretty=%s"])?,
+    ) {
+        (Some(t), Some(s)) => (t, s),
+        _ => {
+            println!("Could not read the latest commit");
+            return Ok(());
+        }
+    };
+
+    // Amending runs the hook again for a commit that already has a checkpoint, so that checkpoint
+    // gets the new subject instead of a second checkpoint being added.
+    let reflog_subject = git_output(&["reflog", "-1", "--format=%gs"])?.unwrap_or_default();
+    if reflog_subject.starts_with("commit (amend)") {
+        let previous_subject = git_output(&["log", "-1", "--pretty=%s", "HEAD@{1}"])?;
+        return amend_commit_checkpoint(previous_subject.as_deref(), &subject, config);
+    }
+
+    match config.git_projects.get(&top_level) {
+        Some(short_name) => add_checkpoint_at(Utc::now().timestamp(), &subject, short_name, config),
+        None => {
+            println!(
+                "Not adding a checkpoint, the repository '{}' is not mapped to a project",
+                top_level
+            );
+            Ok(())
         }
     }
+}
+
+/// Changes the message of the most recent checkpoint from the subject of an amended commit to the
+/// new subject.
+fn amend_commit_checkpoint(
+    previous_subject: Option<&str>,
+    subject: &str,
+    config: &Config,
+) -> io::Result<()> {
+    let mut checkpoint_db = read_database(Path::new(&config.database_path))?;
+
+    let checkpoint = match checkpoint_db.get_checkpoint_mut(&CheckpointId::Position(0)) {
+        Some(checkpoint) if Some(checkpoint.message.as_str()) == previous_subject => checkpoint,
+        _ => {
+            println!("Not adding a checkpoint for the amended commit");
+            return Ok(());
+        }
+    };
+
+    if checkpoint.message != subject {
+        checkpoint.message = subject.to_string();
+        write_database(&checkpoint_db, config)?;
+    }
+    println!(
+        "{} the message of the checkpoint of the amended commit",
+        change_verb(config, "Updated", "Would update")
+    );
 
     Ok(())
 }
@@ -864,7 +2956,87 @@ fn config(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
         config_new.database_path = path.to_string();
     }
 
-    config_new.write()?;
+    if let Some(remind_after) = matches.value_of("remind-after") {
+        match remind_after.parse::<i64>() {
+            Ok(m) => config_new.remind_after = m,
+            Err(e) => {
+                println!("Error when parsing \"remind-after\" argument: {:?}", e);
+                return Ok(());
+            }
+        }
+    }
+
+    for (arg, value) in [
+        ("work-start", &mut config_new.work_start),
+        ("work-end", &mut config_new.work_end),
+    ] {
+        if let Some(time) = matches.value_of(arg) {
+            if let Err(e) = NaiveTime::parse_from_str(time, HM_FORMAT) {
+                println!("Error when parsing \"{}\" argument: {:?}", arg, e);
+                return Ok(());
+            }
+            *value = time.to_string();
+        }
+    }
+
+    if let Some(command) = matches.value_of("remind-command") {
+        config_new.remind_command = if command.is_empty() {
+            None
+        } else {
+            Some(command.to_string())
+        };
+    }
+
+    if let Some(confirm_destructive) = matches.value_of("confirm-destructive") {
+        config_new.confirm_destructive = confirm_destructive == "true";
+    }
+
+    match matches.value_of("day-start") {
+        Some("off") => config_new.day_start = DayStart::Off,
+        Some("ignore") => config_new.day_start = DayStart::Ignore,
+        Some("insert") => config_new.day_start = DayStart::Insert,
+        _ => (),
+    }
+
+    if let Some(max_span) = matches.value_of("max-span") {
+        match max_span.parse::<i64>() {
+            Ok(h) => config_new.max_span_hours = h,
+            Err(e) => {
+                println!("Error when parsing \"max-span\" argument: {:?}", e);
+                return Ok(());
+            }
+        }
+    }
+
+    if config.dry_run {
+        println!("Dry run, the config file was not changed");
+    } else {
+        config_new.write()?;
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_are_parsed_into_seconds() {
+        assert_eq!(parse_offset("30m"), Some(30 * 60));
+        assert_eq!(parse_offset("2h"), Some(2 * 60 * 60));
+        assert_eq!(parse_offset("1h30m"), Some(90 * 60));
+        assert_eq!(parse_offset("45"), Some(45 * 60));
+    }
+
+    #[test]
+    fn invalid_offsets_are_rejected() {
+        assert_eq!(parse_offset(""), None);
+        assert_eq!(parse_offset("h"), None);
+        assert_eq!(parse_offset("1h30"), None);
+        assert_eq!(parse_offset("2 hours"), None);
+        assert_eq!(parse_offset("99999999999999999h"), None);
+        assert_eq!(parse_offset("2000000000000000h2000000000000000h"), None);
+        assert_eq!(parse_offset("999999999999999999"), None);
+    }
+}
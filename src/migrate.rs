@@ -0,0 +1,220 @@
+//! Converting databases written by version 0.2 and older of this program.
+//!
+//! Those databases have events with any number of tags, instead of checkpoints with at most one
+//! project. Each tag becomes a project, and each event becomes a checkpoint with one of its tags
+//! as project and the rest of them in the message.
+
+use crate::{change_verb, write_database, Config};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, io, path::Path};
+use time_track::{CheckpointDb, ProjectId};
+
+/// The suffix of the copy of the old database that is kept after migrating.
+const BACKUP_SUFFIX: &str = ".pre-0.3.bak";
+
+#[derive(Deserialize)]
+struct LegacyTag {
+    long_name: String,
+    short_name: String,
+}
+
+#[derive(Deserialize)]
+struct LegacyEvent {
+    message: String,
+    tag_ids: Vec<u32>,
+}
+
+/// The `EventDb` of version 0.2. Tags are kept in the order of their IDs, which is the order they
+/// were added in.
+#[derive(Deserialize)]
+struct LegacyDb {
+    events: BTreeMap<i64, LegacyEvent>,
+    tags: BTreeMap<u32, LegacyTag>,
+}
+
+fn read_legacy_database(path: &Path) -> Option<LegacyDb> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+/// Returns true if the file is a database written by version 0.2 or older.
+pub fn is_legacy_database(path: &Path) -> bool {
+    read_legacy_database(path).is_some()
+}
+
+/// A checkpoint converted from an event.
+struct MigratedCheckpoint {
+    timestamp: i64,
+    short_name: Option<String>,
+    message: String,
+    /// How many tags of the event were added to the message.
+    other_tags: usize,
+}
+
+/// Converts the events to checkpoints. Events with several tags get the first of their tags that
+/// is in `primary` as project, or their first tag, and the other tags are added to the message.
+fn convert_events(legacy_db: &LegacyDb, primary: &[&str]) -> Vec<MigratedCheckpoint> {
+    legacy_db
+        .events
+        .iter()
+        .map(|(timestamp, event)| {
+            let mut event_tags: Vec<&LegacyTag> = event
+                .tag_ids
+                .iter()
+                .filter_map(|id| legacy_db.tags.get(id))
+                .collect();
+
+            if let Some(i) = primary.iter().find_map(|short_name| {
+                event_tags
+                    .iter()
+                    .position(|tag| tag.short_name == *short_name)
+            }) {
+                let tag = event_tags.remove(i);
+                event_tags.insert(0, tag);
+            }
+
+            let mut message = event.message.clone();
+            let other_tags = event_tags.len().saturating_sub(1);
+            if other_tags > 0 {
+                let other_tags: Vec<&str> = event_tags[1..]
+                    .iter()
+                    .map(|tag| tag.short_name.as_str())
+                    .collect();
+                if !message.is_empty() {
+                    message.push(' ');
+                }
+                message.push_str(&format!("[{}]", other_tags.join(", ")));
+            }
+
+            MigratedCheckpoint {
+                timestamp: *timestamp,
+                short_name: event_tags.first().map(|tag| tag.short_name.clone()),
+                message,
+                other_tags,
+            }
+        })
+        .collect()
+}
+
+/// Converts a database written by version 0.2 or older. The old database is kept next to the new
+/// one.
+pub fn migrate(matches: &clap::ArgMatches, config: &Config) -> io::Result<()> {
+    let path = Path::new(&config.database_path);
+    let legacy_db = match read_legacy_database(path) {
+        Some(legacy_db) => legacy_db,
+        None => {
+            println!("The database does not need to be migrated");
+            return Ok(());
+        }
+    };
+
+    let backup_path = format!("{}{}", config.database_path, BACKUP_SUFFIX);
+    if Path::new(&backup_path).exists() {
+        println!(
+            "Can't migrate, there already is a file at the backup path '{}'",
+            backup_path
+        );
+        return Ok(());
+    }
+
+    let primary: Vec<&str> = matches
+        .value_of("primary")
+        .unwrap_or("")
+        .split_whitespace()
+        .collect();
+
+    let mut checkpoint_db = CheckpointDb::default();
+
+    for tag in legacy_db.tags.values() {
+        if let Err(e) = checkpoint_db.add_project(&tag.long_name, &tag.short_name) {
+            println!(
+                "Could not add project with short name '{short}': {error}",
+                short = tag.short_name,
+                error = e,
+            );
+            return Ok(());
+        }
+    }
+
+    let checkpoints = convert_events(&legacy_db, &primary);
+    for checkpoint in &checkpoints {
+        let project_id = checkpoint
+            .short_name
+            .as_ref()
+            .and_then(|short_name| checkpoint_db.project_id_from_short_name(short_name))
+            .unwrap_or(ProjectId::NoId);
+
+        checkpoint_db
+            .add_checkpoint(checkpoint.timestamp, &checkpoint.message, project_id)
+            .unwrap();
+    }
+
+    // The old database is copied rather than moved, so there still is a database if writing the
+    // new one fails.
+    if !config.dry_run {
+        fs::copy(path, &backup_path)?;
+    }
+    write_database(&checkpoint_db, config)?;
+
+    println!(
+        "{} {} tags to projects and {} events to checkpoints, {} events had more than one tag",
+        change_verb(config, "Migrated", "Would migrate"),
+        legacy_db.tags.len(),
+        checkpoints.len(),
+        checkpoints
+            .iter()
+            .filter(|checkpoint| checkpoint.other_tags > 0)
+            .count()
+    );
+    if !config.dry_run {
+        println!("The old database was copied to '{}'", backup_path);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATABASE_0_2: &str = include_str!("../tests/fixtures/database-0.2.json");
+
+    #[test]
+    fn tags_keep_their_order() {
+        let legacy_db: LegacyDb = serde_json::from_str(DATABASE_0_2).unwrap();
+        let short_names: Vec<&str> = legacy_db
+            .tags
+            .values()
+            .map(|tag| tag.short_name.as_str())
+            .collect();
+
+        assert_eq!(short_names, ["int", "client", "rev"]);
+    }
+
+    #[test]
+    fn events_become_checkpoints() {
+        let legacy_db: LegacyDb = serde_json::from_str(DATABASE_0_2).unwrap();
+        let checkpoints = convert_events(&legacy_db, &[]);
+
+        assert_eq!(checkpoints.len(), 4);
+        assert_eq!(checkpoints[0].short_name, None);
+        assert_eq!(checkpoints[1].short_name.as_deref(), Some("int"));
+        assert_eq!(checkpoints[1].message, "Planning");
+        assert_eq!(checkpoints[2].short_name.as_deref(), Some("rev"));
+        assert_eq!(checkpoints[2].message, "Review [client]");
+    }
+
+    #[test]
+    fn primary_tags_become_the_project() {
+        let legacy_db: LegacyDb = serde_json::from_str(DATABASE_0_2).unwrap();
+        let checkpoints = convert_events(&legacy_db, &["client"]);
+
+        assert_eq!(checkpoints[2].short_name.as_deref(), Some("client"));
+        assert_eq!(checkpoints[2].message, "Review [rev]");
+    }
+
+    #[test]
+    fn current_databases_are_not_legacy() {
+        let current = r#"{"checkpoints": {}, "projects": {}}"#;
+        assert!(serde_json::from_str::<LegacyDb>(current).is_err());
+    }
+}